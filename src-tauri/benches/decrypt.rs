@@ -0,0 +1,41 @@
+//! AES-128-CBC 分片解密吞吐量
+//!
+//! 默认构建在 x86/x86_64 上运行时检测并使用 AES-NI；与软件实现对比时强制使用软件实现：
+//! `RUSTFLAGS="--cfg aes_force_soft" cargo bench --features bench --bench decrypt`
+//! aarch64 上开启硬件实现：`RUSTFLAGS="--cfg aes_armv8"`。
+
+use aes::Aes128;
+use cbc::Encryptor;
+use cipher::{block_padding::Pkcs7, BlockEncryptMut, KeyIvInit};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use downloader_lib::bench::decrypt_aes128;
+
+const KEY: [u8; 16] = [0x11; 16];
+const IV: [u8; 16] = [0x22; 16];
+
+fn encrypt(plain: &[u8]) -> Vec<u8> {
+    let mut buffer = plain.to_vec();
+    buffer.resize(plain.len() + 16, 0);
+    let len = Encryptor::<Aes128>::new(&KEY.into(), &IV.into())
+        .encrypt_padded_mut::<Pkcs7>(&mut buffer, plain.len())
+        .unwrap()
+        .len();
+    buffer.truncate(len);
+    buffer
+}
+
+fn bench_decrypt(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decrypt_aes128");
+    // 常见分片大小：低码率约 512 KB，高码率约 2 MB
+    for size in [512 * 1024, 2 * 1024 * 1024] {
+        let ciphertext = encrypt(&vec![0x47; size]);
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &ciphertext, |b, data| {
+            b.iter(|| decrypt_aes128(data, &KEY, &IV).unwrap());
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_decrypt);
+criterion_main!(benches);
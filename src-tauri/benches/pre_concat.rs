@@ -0,0 +1,42 @@
+//! 合并前的预拼接：50k 个小分片按组并行拼接，与全部顺序拼接对比
+//!
+//! 运行：`cargo bench --features bench --bench pre_concat`
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use downloader_lib::bench::{concat_all, pre_concat};
+
+/// 分片数量
+const SEGMENTS: usize = 50_000;
+/// 每个分片的大小（字节），模拟很短的分片
+const SEGMENT_SIZE: usize = 188 * 8;
+
+fn bench_pre_concat(c: &mut Criterion) {
+    let dir = std::env::temp_dir().join(format!("m3u8_bench_concat_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let segment = vec![0x47; SEGMENT_SIZE];
+    let files: Vec<String> = (0..SEGMENTS)
+        .map(|index| {
+            let path = dir.join(format!("part_{}.ts", index));
+            std::fs::write(&path, &segment).unwrap();
+            path.to_string_lossy().into_owned()
+        })
+        .collect();
+    let temp_dir = dir.to_string_lossy().into_owned();
+    let output = dir.join("output.ts").to_string_lossy().into_owned();
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+
+    let mut group = c.benchmark_group("concat_50k");
+    group.sample_size(10);
+    group.bench_function("sequential", |b| {
+        b.to_async(&runtime).iter(|| concat_all(&files, &output));
+    });
+    group.bench_function("pre_concat", |b| {
+        b.to_async(&runtime).iter(|| pre_concat(&files, &temp_dir));
+    });
+    group.finish();
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+criterion_group!(benches, bench_pre_concat);
+criterion_main!(benches);
@@ -0,0 +1,58 @@
+//! 断点续传扫描：20k 个分片的临时目录，逐个检查与并发检查对比
+//!
+//! 运行：`cargo bench --features bench --bench resume_scan`
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use downloader_lib::bench::{resume_scan, RESUME_SCAN_DEFAULT_CONCURRENCY};
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+/// 分片数量
+const SEGMENTS: usize = 20_000;
+/// 每个分片的 TS 包数
+const PACKETS_PER_SEGMENT: usize = 4;
+
+/// 创建临时目录：一半分片记录在清单中，另一半不在清单中、需要读取内容判断能否采用
+fn prepare(dir: &PathBuf) -> (Vec<String>, HashSet<String>) {
+    std::fs::create_dir_all(dir).unwrap();
+    let mut packet = [0xFFu8; 188];
+    packet[0] = 0x47;
+    let segment = packet.repeat(PACKETS_PER_SEGMENT);
+    let mut files = Vec::with_capacity(SEGMENTS);
+    let mut listed = HashSet::new();
+    for index in 0..SEGMENTS {
+        let name = format!("part_{}.ts", index);
+        let path = dir.join(&name);
+        std::fs::write(&path, &segment).unwrap();
+        files.push(path.to_string_lossy().into_owned());
+        if index % 2 == 0 {
+            listed.insert(name);
+        }
+    }
+    (files, listed)
+}
+
+fn bench_resume_scan(c: &mut Criterion) {
+    let dir = std::env::temp_dir().join(format!("m3u8_bench_scan_{}", std::process::id()));
+    let (files, listed) = prepare(&dir);
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+
+    let mut group = c.benchmark_group("resume_scan_20k");
+    group.sample_size(10);
+    for concurrency in [1, RESUME_SCAN_DEFAULT_CONCURRENCY] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(concurrency),
+            &concurrency,
+            |b, &concurrency| {
+                b.to_async(&runtime)
+                    .iter(|| resume_scan(&files, &listed, concurrency));
+            },
+        );
+    }
+    group.finish();
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+criterion_group!(benches, bench_resume_scan);
+criterion_main!(benches);
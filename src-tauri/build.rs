@@ -1,3 +1,5 @@
 fn main() {
+    // .cargo/config.toml 中为 aarch64 目标设置，供诊断代码判断是否启用了硬件 AES
+    println!("cargo:rustc-check-cfg=cfg(aes_armv8)");
     tauri_build::build()
 }
@@ -0,0 +1,43 @@
+//! 基准测试入口
+//! 下载、解密、合并的实现都在私有模块中，这里只为 `benches/` 导出需要测量的函数，
+//! 仅在启用 `bench` 特性时编译，不属于公开接口。
+
+use crate::download::{scan_segment, ResumeState, RESUME_SCAN_CONCURRENCY};
+use crate::download_manager::DownloadControl;
+use futures::StreamExt;
+use std::collections::HashSet;
+
+/// 断点续传扫描的默认并发数
+pub const RESUME_SCAN_DEFAULT_CONCURRENCY: usize = RESUME_SCAN_CONCURRENCY;
+
+/// 按断点续传的方式扫描分片文件，返回无需重新下载的分片数
+///
+/// `concurrency` 为 1 时逐个检查，用于与并发扫描对比。
+pub async fn resume_scan(
+    files: &[String],
+    completed_segment_names: &HashSet<String>,
+    concurrency: usize,
+) -> usize {
+    futures::stream::iter(files)
+        .map(|file| scan_segment(file, completed_segment_names, true))
+        .buffered(concurrency.max(1))
+        .filter(|(_, state)| std::future::ready(!matches!(state, ResumeState::Pending)))
+        .count()
+        .await
+}
+
+/// AES-128-CBC 解密一个分片（PKCS7 填充）
+pub fn decrypt_aes128(data: &[u8], key: &[u8], iv: &[u8]) -> anyhow::Result<Vec<u8>> {
+    crate::download::decrypt_aes128(data, key, iv, false)
+}
+
+/// 把全部分片按字节拼接到一个文件，不分组
+pub async fn concat_all(files: &[String], output: &str) -> anyhow::Result<()> {
+    crate::merge::concat_segment_group(files, output).await
+}
+
+/// 合并前的并行预拼接，返回中间文件列表
+pub async fn pre_concat(files: &[String], temp_dir: &str) -> anyhow::Result<Vec<String>> {
+    let control = DownloadControl::new();
+    crate::merge::pre_concat_segments("bench", files, temp_dir, &[], &control).await
+}
@@ -1,70 +1,708 @@
-use crate::download::{download_m3u8, DownloadOptions};
-use crate::download_manager::{DownloadManager, DownloadTask};
+use crate::data_usage::{self, DataUsage};
+use crate::diagnostics::{run_self_test, SelfTestReport};
+use crate::disk_space::{DEFAULT_MIN_FREE_SPACE, DEFAULT_SEGMENT_SIZE_ESTIMATE};
+use crate::download::{
+    download_m3u8, list_playlist_tracks, local_playlist_path, merge_existing_segments,
+    probe_playlist, verify_segments, DownloadOptions, PlaylistProbe, VerifyDepth, VerifyReport,
+    DEFAULT_MAX_BACKOFF, DEFAULT_MAX_REDIRECTS, DEFAULT_MAX_RETRIES, DEFAULT_SEGMENT_TIMEOUT,
+};
+use crate::download_manager::{
+    DownloadControl, DownloadManager, DownloadTask, MetricsSlot, PauseMode, TaskStatus,
+    TempDirCleanup, DEFAULT_MAX_CONCURRENT_TASKS,
+};
+use crate::logger::{self, rotate::get_log_dir_path};
+use crate::m3u8::{PlaylistTracks, VariantQuality};
+use crate::merge::{FfmpegSource, OutputFormat, TimestampMode};
+use crate::notification::notify_task_failed;
+use crate::task_events::{emit_task_event, emit_task_update, TaskPhase, TaskUpdate};
+use crate::task_registry::{self, IncompleteTask, TaskRecord};
+use crate::tray;
 use anyhow::Result;
+use log::LevelFilter;
+use serde::Deserialize;
 use serde_json::Value;
-use std::fs;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Duration;
 use sysinfo::{System, SystemExt};
-use tauri::{AppHandle, Emitter};
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_opener::OpenerExt;
 use tauri_plugin_store::StoreExt;
 use tauri_plugin_updater::UpdaterExt;
 
+/// 单个下载线程数上限
+const MAX_THREAD_COUNT: usize = 1024;
+
+/// `start_download` 命令的输入参数
+///
+/// 前端以 `{ input: {...} }` 的形式传入，字段名使用 camelCase，
+/// 在创建任务之前通过 [`StartDownloadInput::validate`] 统一校验。
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StartDownloadInput {
+    pub id: String,
+    pub url: String,
+    pub name: String,
+    pub output_dir: String,
+    pub thread_count: usize,
+    #[serde(default)]
+    pub headers: Option<HashMap<String, String>>, // 自定义请求头
+    #[serde(default)]
+    pub key_headers: Option<HashMap<String, String>>, // 密钥专用请求头
+    #[serde(default)]
+    pub force_fresh: bool, // 忽略断点续传数据，重新下载
+    #[serde(default)]
+    pub auto_reencode: Option<bool>, // 编码不兼容 MP4 时是否自动重新编码，未指定时读取设置，默认开启
+    #[serde(default)]
+    pub extend_on_resume: bool, // 断点续传时追加播放列表中新增的分片
+    #[serde(default)]
+    pub ignore_segment_query: Option<bool>, // 追加新增分片时忽略分片地址的查询参数，未指定时读取设置，默认关闭
+    #[serde(default)]
+    pub revalidate_on_resume: bool, // 断点续传时校验密钥，密钥变化的分片重新下载
+    #[serde(default)]
+    pub allow_unpadded_decrypt: bool, // 去填充失败时尝试无填充解密
+    #[serde(default)]
+    pub safe_concat: bool, // 合并时使用相对路径，不传 -safe 0
+    #[serde(default)]
+    pub pre_concat: Option<bool>, // 合并前按组预拼接分片，默认分片很多时自动开启
+    #[serde(default)]
+    pub timestamp_mode: Option<TimestampMode>, // 时间戳处理: auto/off/genpts/copyts，未指定时读取设置，默认 auto
+    #[serde(default)]
+    pub output_format: Option<OutputFormat>, // 输出格式: mp4/mkv/ts，未指定时读取设置，默认 mp4
+    #[serde(default)]
+    pub overwrite: Option<bool>, // 同名文件已存在时覆盖，否则追加序号，未指定时读取设置，默认不覆盖
+    #[serde(default)]
+    pub generate_thumbnail: Option<bool>, // 合并后截取封面图片，未指定时读取设置，默认关闭
+    #[serde(default)]
+    pub keep_temp: Option<bool>, // 合并成功后保留临时目录中的分片，未指定时读取设置，默认不保留
+    #[serde(default)]
+    pub use_system_ffmpeg: Option<bool>, // 使用系统 ffmpeg 而非自带的，未指定时读取设置
+    #[serde(default)]
+    pub ffmpeg_path: Option<String>, // 系统 ffmpeg 路径，为空时在 PATH 中查找
+    #[serde(default)]
+    pub max_redirects: Option<usize>, // 最大重定向次数，未指定时读取设置，默认 10
+    #[serde(default)]
+    pub quality: Option<VariantQuality>, // 主播放列表画质: highest/lowest/1080p，默认读取设置
+    #[serde(default)]
+    pub audio_language: Option<String>, // 音频单独存放时优先选择的语言，未指定时读取设置
+    #[serde(default)]
+    pub connect_timeout_secs: Option<u64>, // 连接超时（秒），默认 30
+    #[serde(default)]
+    pub segment_timeout_secs: Option<u64>, // 单个分片请求超时（秒），未指定时读取设置，默认 60
+    #[serde(default)]
+    pub proxy: Option<String>, // 代理地址（http:// 或 socks5://），未指定时读取设置
+    #[serde(default)]
+    pub danger_accept_invalid_certs: Option<bool>, // 不校验 TLS 证书（自签名证书），未指定时读取设置，默认关闭
+    #[serde(default)]
+    pub user_agent: Option<String>, // User-Agent，未指定时读取设置，为空使用浏览器默认值
+    #[serde(default)]
+    pub base_url: Option<String>, // 本地播放列表中相对地址的基准地址，网络地址时忽略
+    #[serde(default)]
+    pub cookies: Option<String>, // 初始 Cookie（name=value; name2=value2），设置后任务使用独立的 Cookie 存储
+    #[serde(default)]
+    pub max_speed_bytes_per_sec: Option<u64>, // 任务限速（字节/秒），未指定时读取设置，0 不限速
+    #[serde(default)]
+    pub max_connections_per_host: Option<usize>, // 单个主机的最大连接数，未指定时读取设置，0 与并发数相同
+    #[serde(default)]
+    pub estimate_total_size: Option<bool>, // 抽样估算总大小，提供按字节的进度，默认开启
+    #[serde(default)]
+    pub max_retries: Option<usize>, // 单个分片最大尝试次数，未指定时读取设置，默认 99
+    #[serde(default)]
+    pub max_backoff_secs: Option<u64>, // 重试退避上限（秒），未指定时读取设置，默认 10
+    #[serde(default)]
+    pub deterministic_backoff: Option<bool>, // 重试退避不加随机抖动，未指定时读取设置，默认关闭
+    #[serde(default)]
+    pub fail_fast: Option<bool>, // 分片用尽重试后立即取消任务，未指定时读取设置，默认开启
+    #[serde(default)]
+    pub tolerate_missing: Option<bool>, // 跳过最终失败的分片继续合并，未指定时读取设置，默认关闭
+    #[serde(default)]
+    pub skip_invalid_segments: Option<bool>, // 跳过内容为空或无效的分片继续合并，未指定时读取设置，默认关闭
+    #[serde(default)]
+    pub check_disk_space: Option<bool>, // 下载前检查磁盘剩余空间，未指定时读取设置，默认开启
+    #[serde(default)]
+    pub min_free_space_mb: Option<u64>, // 下载中剩余空间低于该值（MB）时暂停，默认 512
+    #[serde(default)]
+    pub segment_size_estimate_mb: Option<u64>, // 无法获取分片大小时的单分片估算（MB），默认 2
+    #[serde(default)]
+    pub live: Option<bool>, // 录制直播流直到结束，未指定时读取设置，默认关闭（遇到直播流报错）
+}
+
+impl StartDownloadInput {
+    /// 校验输入参数，返回第一个不合法字段的具体原因
+    pub fn validate(&self) -> Result<(), String> {
+        if self.id.trim().is_empty() {
+            return Err("任务ID不能为空".into());
+        }
+        if self.url.trim().is_empty() {
+            return Err("下载地址不能为空".into());
+        }
+        let url_lower = self.url.trim().to_lowercase();
+        if !(url_lower.starts_with("http://")
+            || url_lower.starts_with("https://")
+            || local_playlist_path(&self.url).is_some())
+        {
+            return Err(format!(
+                "下载地址必须以 http://、https:// 或 file:// 开头，或为本地文件的绝对路径: {}",
+                self.url
+            ));
+        }
+        if let Some(base_url) = self.base_url.as_deref().map(str::trim) {
+            let base_lower = base_url.to_lowercase();
+            if !base_url.is_empty()
+                && !(base_lower.starts_with("http://") || base_lower.starts_with("https://"))
+            {
+                return Err(format!(
+                    "基础地址必须以 http:// 或 https:// 开头: {}",
+                    base_url
+                ));
+            }
+        }
+        if self.name.trim().is_empty() {
+            return Err("文件名不能为空".into());
+        }
+        if self.output_dir.trim().is_empty() {
+            return Err("保存目录不能为空".into());
+        }
+        if self.thread_count == 0 || self.thread_count > MAX_THREAD_COUNT {
+            return Err(format!(
+                "线程数必须在 1 到 {} 之间，当前为 {}",
+                MAX_THREAD_COUNT, self.thread_count
+            ));
+        }
+        Ok(())
+    }
+}
+
 #[tauri::command]
 pub async fn start_download(
+    input: StartDownloadInput,
+    app_handle: AppHandle,
+    manager: tauri::State<'_, DownloadManager>, // 注入全局管理器
+) -> Result<(), String> {
+    // 参数校验在创建任何任务之前完成
+    input.validate()?;
+    start_download_input(input, &app_handle, &manager).await
+}
+
+/// 登记并运行一个参数已校验的下载任务
+async fn start_download_input(
+    input: StartDownloadInput,
+    app_handle: &AppHandle,
+    manager: &DownloadManager,
+) -> Result<(), String> {
+    let download = register_download(input, app_handle, manager).await?;
+    run_download(download, app_handle, manager).await
+}
+
+/// 已登记到管理器、等待开始下载的任务
+struct PreparedDownload {
     id: String,
     url: String,
     name: String,
+    temp_dir: String,
     output_dir: String,
     thread_count: usize,
-    app_handle: AppHandle,
-    manager: tauri::State<'_, DownloadManager>, // 注入全局管理器
-    headers: Option<std::collections::HashMap<String, String>>, // 自定义请求头
-) -> Result<(), String> {
-    
+    control: Arc<DownloadControl>,
+    metrics_slot: MetricsSlot,
+    options: DownloadOptions,
+}
+
+/// 设置中的“编码不兼容时自动重新编码”
+fn auto_reencode_setting(app_handle: &AppHandle) -> Option<bool> {
+    app_handle
+        .store("settings.dat")
+        .ok()
+        .and_then(|s| s.get("autoReencode"))
+        .and_then(|v| v.as_bool())
+}
+
+/// 设置中的时间戳处理方式
+fn timestamp_mode_setting(app_handle: &AppHandle) -> Option<TimestampMode> {
+    app_handle
+        .store("settings.dat")
+        .ok()
+        .and_then(|s| s.get("timestampMode"))
+        .and_then(|v| serde_json::from_value(v).ok())
+}
+
+/// 设置中的最大重定向次数
+fn max_redirects_setting(app_handle: &AppHandle) -> Option<usize> {
+    app_handle
+        .store("settings.dat")
+        .ok()
+        .and_then(|s| s.get("maxRedirects"))
+        .and_then(|v| v.as_u64())
+        .map(|v| v as usize)
+}
+
+/// 设置中的代理地址，空字符串表示不使用代理
+fn proxy_setting(app_handle: &AppHandle) -> Option<String> {
+    app_handle
+        .store("settings.dat")
+        .ok()
+        .and_then(|s| s.get("proxy"))
+        .and_then(|v| v.as_str().map(|s| s.to_string()))
+}
+
+/// 设置中的“不校验证书”
+fn accept_invalid_certs_setting(app_handle: &AppHandle) -> Option<bool> {
+    app_handle
+        .store("settings.dat")
+        .ok()
+        .and_then(|s| s.get("dangerAcceptInvalidCerts"))
+        .and_then(|v| v.as_bool())
+}
+
+/// 设置中的 User-Agent，空字符串表示使用默认值
+fn user_agent_setting(app_handle: &AppHandle) -> Option<String> {
+    app_handle
+        .store("settings.dat")
+        .ok()
+        .and_then(|s| s.get("userAgent"))
+        .and_then(|v| v.as_str().map(|s| s.to_string()))
+}
+
+/// 按设置中的代理、User-Agent、重定向次数和证书校验创建请求播放列表用的下载选项
+///
+/// 预览播放列表、列出轨道与开始下载使用同样的网络配置，结果才与实际下载一致。
+fn playlist_request_options(app_handle: &AppHandle) -> DownloadOptions {
+    let mut options = DownloadOptions::new();
+    options.max_redirects = max_redirects_setting(app_handle).unwrap_or(DEFAULT_MAX_REDIRECTS);
+    options.proxy = proxy_setting(app_handle).filter(|p| !p.trim().is_empty());
+    options.danger_accept_invalid_certs = accept_invalid_certs_setting(app_handle).unwrap_or(false);
+    options.user_agent = user_agent_setting(app_handle)
+        .map(|ua| ua.trim().to_string())
+        .filter(|ua| !ua.is_empty());
+    options
+}
+
+/// 把任务登记到管理器并加入排队，返回下载所需的参数；同 ID 的任务已在运行时返回错误
+///
+/// 登记时即按调用顺序排队，批量添加的任务按添加顺序开始下载。
+async fn register_download(
+    input: StartDownloadInput,
+    app_handle: &AppHandle,
+    manager: &DownloadManager,
+) -> Result<PreparedDownload, String> {
+    let StartDownloadInput {
+        id,
+        url,
+        name,
+        output_dir,
+        thread_count,
+        headers,
+        key_headers,
+        force_fresh,
+        auto_reencode,
+        extend_on_resume,
+        ignore_segment_query,
+        revalidate_on_resume,
+        allow_unpadded_decrypt,
+        safe_concat,
+        pre_concat,
+        timestamp_mode,
+        output_format,
+        overwrite,
+        generate_thumbnail,
+        keep_temp,
+        use_system_ffmpeg,
+        ffmpeg_path,
+        max_redirects,
+        quality,
+        audio_language,
+        connect_timeout_secs,
+        segment_timeout_secs,
+        proxy,
+        danger_accept_invalid_certs,
+        user_agent,
+        base_url,
+        cookies,
+        max_speed_bytes_per_sec,
+        max_connections_per_host,
+        estimate_total_size,
+        max_retries,
+        max_backoff_secs,
+        deterministic_backoff,
+        fail_fast,
+        tolerate_missing,
+        skip_invalid_segments,
+        check_disk_space,
+        min_free_space_mb,
+        segment_size_estimate_mb,
+        live,
+    } = input;
+
     if manager.task_exists(&id).await {
-            log::warn!("任务 [{}] 已在运行中，忽略本次请求", id);
-            return Ok(());
+        log::warn!("任务 [{}] 已在运行中，拒绝重复添加", id);
+        return Err(format!("任务 [{}] 已在下载中，请勿重复添加", id));
     }
-    
+
     let temp_dir = format!("{}/temp_{}", output_dir, id);
 
     log::info!("Name: [{}], URL: [{}], ID: [{}] - 开始下载", name, url, id);
 
-    // 检查临时目录是否存在，不存在则创建
-    let temp_dir_exists = tokio::fs::try_exists(&temp_dir).await.unwrap_or(false);
-    if !temp_dir_exists {
-        fs::create_dir_all(&temp_dir).map_err(|e| format!("创建临时目录失败: {}", e))?;
-        app_handle
-            .emit(
-                "create_temp_directory",
-                serde_json::json!({
-                                "id": id,
-                                "isCreatedTempDir": true,
-                                "message": "已创建临时下载目录",
-                }),
-            )
-            .ok();
-        log::info!("任务 [{}] 已创建临时目录: {}", id, &temp_dir);
-    } else {
-        log::info!("任务 [{}] 临时目录已存在，继续下载: {}", id, &temp_dir);
-    }
-
-    // 创建任务并添加到管理器
-    let task = DownloadTask::new(temp_dir.clone());
-    let cancelled = task.get_cancel_flag();
+    // 创建任务并添加到管理器（临时目录由管理器在登记时统一创建）
+    let task = DownloadTask::new(name.clone(), temp_dir.clone());
+    let control = task.get_control();
+    let metrics_slot = task.metrics_slot();
 
-    manager
+    let temp_dir_created = manager
         .add_task(id.clone(), task)
         .await
         .map_err(|e| e.to_string())?;
+    tray::refresh_tooltip(app_handle).await;
+
+    // 登记任务，应用意外退出后可以继续下载
+    let record = TaskRecord {
+        id: id.clone(),
+        url: url.clone(),
+        name: name.clone(),
+        output_dir: output_dir.clone(),
+        thread_count,
+        headers: headers.clone().unwrap_or_default(),
+    };
+    if let Err(e) = task_registry::save_task(app_handle, &record) {
+        log::warn!("任务 [{}] 登记失败: {}", id, e);
+    }
+
+    if temp_dir_created {
+        emit_task_event(
+            app_handle,
+            "create_temp_directory",
+            serde_json::json!({
+                "id": id,
+                "isCreatedTempDir": true,
+                "message": "已创建临时下载目录",
+            }),
+            TaskUpdate::new(&id, TaskPhase::Preparing, 2, "已创建临时下载目录"),
+        )
+        .ok();
+        log::info!("任务 [{}] 已创建临时目录: {}", id, &temp_dir);
+    } else {
+        log::info!("任务 [{}] 临时目录已存在，继续下载: {}", id, &temp_dir);
+    }
 
     // 创建下载选项
     let mut options = DownloadOptions::new();
     if let Some(headers_map) = headers {
         options.headers = headers_map;
     }
+    options.key_headers = key_headers;
+    options.force_fresh = force_fresh;
+    if let Some(auto_reencode) = auto_reencode.or_else(|| auto_reencode_setting(app_handle)) {
+        options.auto_reencode = auto_reencode;
+    }
+    options.extend_on_resume = extend_on_resume;
+    options.ignore_segment_query = ignore_segment_query
+        .or_else(|| {
+            app_handle
+                .store("settings.dat")
+                .ok()
+                .and_then(|s| s.get("ignoreSegmentQuery"))
+                .and_then(|v| v.as_bool())
+        })
+        .unwrap_or(false);
+    options.revalidate_on_resume = revalidate_on_resume;
+    options.allow_unpadded_decrypt = allow_unpadded_decrypt;
+    options.safe_concat = safe_concat;
+    options.pre_concat = pre_concat;
+    if let Some(timestamp_mode) = timestamp_mode.or_else(|| timestamp_mode_setting(app_handle)) {
+        options.timestamp_mode = timestamp_mode;
+    }
+    options.output_format = output_format
+        .or_else(|| {
+            app_handle
+                .store("settings.dat")
+                .ok()
+                .and_then(|s| s.get("outputFormat"))
+                .and_then(|v| v.as_str().map(|s| s.to_string()))
+                .and_then(|v| OutputFormat::try_from(v).ok())
+        })
+        .unwrap_or_default();
+    options.overwrite = overwrite
+        .or_else(|| {
+            app_handle
+                .store("settings.dat")
+                .ok()
+                .and_then(|s| s.get("overwriteExisting"))
+                .and_then(|v| v.as_bool())
+        })
+        .unwrap_or(false);
+    options.generate_thumbnail = generate_thumbnail
+        .or_else(|| {
+            app_handle
+                .store("settings.dat")
+                .ok()
+                .and_then(|s| s.get("generateThumbnail"))
+                .and_then(|v| v.as_bool())
+        })
+        .unwrap_or(false);
+    options.keep_temp = keep_temp
+        .or_else(|| {
+            app_handle
+                .store("settings.dat")
+                .ok()
+                .and_then(|s| s.get("keepTemp"))
+                .and_then(|v| v.as_bool())
+        })
+        .unwrap_or(false);
+    // 指定了路径即使用系统 ffmpeg，都未指定时读取设置
+    let ffmpeg_path = ffmpeg_path.filter(|p| !p.trim().is_empty());
+    options.ffmpeg = match (use_system_ffmpeg, ffmpeg_path) {
+        (Some(false), _) => FfmpegSource::Bundled,
+        (_, Some(path)) => FfmpegSource::System(Some(PathBuf::from(path.trim()))),
+        (Some(true), None) => FfmpegSource::System(None),
+        (None, None) => FfmpegSource::from_settings(app_handle),
+    };
+    options.max_redirects = max_redirects
+        .or_else(|| max_redirects_setting(app_handle))
+        .unwrap_or(DEFAULT_MAX_REDIRECTS);
+    options.quality = quality
+        .or_else(|| {
+            app_handle
+                .store("settings.dat")
+                .ok()
+                .and_then(|s| s.get("quality"))
+                .and_then(|v| v.as_str().map(|s| s.to_string()))
+                .and_then(|v| VariantQuality::try_from(v).ok())
+        })
+        .unwrap_or_default();
+    options.audio_language = audio_language
+        .or_else(|| {
+            app_handle
+                .store("settings.dat")
+                .ok()
+                .and_then(|s| s.get("audioLanguage"))
+                .and_then(|v| v.as_str().map(|s| s.to_string()))
+        })
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty());
+    if let Some(secs) = connect_timeout_secs.filter(|&secs| secs > 0) {
+        options.connect_timeout = Duration::from_secs(secs);
+    }
+    options.segment_timeout = segment_timeout_secs
+        .or_else(|| {
+            app_handle
+                .store("settings.dat")
+                .ok()
+                .and_then(|s| s.get("segmentTimeoutSecs"))
+                .and_then(|v| v.as_u64())
+        })
+        .filter(|&secs| secs > 0)
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_SEGMENT_TIMEOUT);
+    // 空字符串表示不使用代理
+    options.proxy = proxy
+        .or_else(|| proxy_setting(app_handle))
+        .filter(|p| !p.trim().is_empty());
+    options.danger_accept_invalid_certs = danger_accept_invalid_certs
+        .or_else(|| accept_invalid_certs_setting(app_handle))
+        .unwrap_or(false);
+    // 空字符串表示使用默认 User-Agent，请求头中的 User-Agent 仍然优先
+    options.user_agent = user_agent
+        .or_else(|| user_agent_setting(app_handle))
+        .map(|ua| ua.trim().to_string())
+        .filter(|ua| !ua.is_empty());
+    options.base_url = base_url
+        .map(|b| b.trim().to_string())
+        .filter(|b| !b.is_empty());
+    // Cookie 因站点而异，不读取全局设置
+    options.cookies = cookies;
+    // 设置中以 KB/s 保存
+    options.max_speed_bytes_per_sec = max_speed_bytes_per_sec
+        .or_else(|| {
+            app_handle
+                .store("settings.dat")
+                .ok()
+                .and_then(|s| s.get("speedLimitKBps"))
+                .and_then(|v| v.as_u64())
+                .map(|kbps| kbps * 1024)
+        })
+        .filter(|&limit| limit > 0);
+    options.max_connections_per_host = max_connections_per_host
+        .or_else(|| {
+            app_handle
+                .store("settings.dat")
+                .ok()
+                .and_then(|s| s.get("maxConnectionsPerHost"))
+                .and_then(|v| v.as_u64())
+                .map(|v| v as usize)
+        })
+        .filter(|&limit| limit > 0);
+    if let Some(estimate_total_size) = estimate_total_size {
+        options.estimate_total_size = estimate_total_size;
+    }
+    options.max_retries = max_retries
+        .or_else(|| {
+            app_handle
+                .store("settings.dat")
+                .ok()
+                .and_then(|s| s.get("maxRetries"))
+                .and_then(|v| v.as_u64())
+                .map(|v| v as usize)
+        })
+        .filter(|&retries| retries > 0)
+        .unwrap_or(DEFAULT_MAX_RETRIES);
+    options.max_backoff = max_backoff_secs
+        .or_else(|| {
+            app_handle
+                .store("settings.dat")
+                .ok()
+                .and_then(|s| s.get("maxBackoffSecs"))
+                .and_then(|v| v.as_u64())
+        })
+        .filter(|&secs| secs > 0)
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_MAX_BACKOFF);
+    options.deterministic_backoff = deterministic_backoff
+        .or_else(|| {
+            app_handle
+                .store("settings.dat")
+                .ok()
+                .and_then(|s| s.get("deterministicBackoff"))
+                .and_then(|v| v.as_bool())
+        })
+        .unwrap_or(false);
+    options.fail_fast = fail_fast
+        .or_else(|| {
+            app_handle
+                .store("settings.dat")
+                .ok()
+                .and_then(|s| s.get("failFast"))
+                .and_then(|v| v.as_bool())
+        })
+        .unwrap_or(true);
+    options.tolerate_missing = tolerate_missing
+        .or_else(|| {
+            app_handle
+                .store("settings.dat")
+                .ok()
+                .and_then(|s| s.get("tolerateMissing"))
+                .and_then(|v| v.as_bool())
+        })
+        .unwrap_or(false);
+    options.skip_invalid_segments = skip_invalid_segments
+        .or_else(|| {
+            app_handle
+                .store("settings.dat")
+                .ok()
+                .and_then(|s| s.get("skipInvalidSegments"))
+                .and_then(|v| v.as_bool())
+        })
+        .unwrap_or(false);
+    options.check_disk_space = check_disk_space
+        .or_else(|| {
+            app_handle
+                .store("settings.dat")
+                .ok()
+                .and_then(|s| s.get("checkDiskSpace"))
+                .and_then(|v| v.as_bool())
+        })
+        .unwrap_or(true);
+    // 设置中以 MB 保存
+    options.min_free_space = min_free_space_mb
+        .or_else(|| {
+            app_handle
+                .store("settings.dat")
+                .ok()
+                .and_then(|s| s.get("minFreeSpaceMB"))
+                .and_then(|v| v.as_u64())
+        })
+        .map(|mb| mb * 1024 * 1024)
+        .unwrap_or(DEFAULT_MIN_FREE_SPACE);
+    options.live = live
+        .or_else(|| {
+            app_handle
+                .store("settings.dat")
+                .ok()
+                .and_then(|s| s.get("liveRecording"))
+                .and_then(|v| v.as_bool())
+        })
+        .unwrap_or(false);
+    options.segment_size_estimate = segment_size_estimate_mb
+        .or_else(|| {
+            app_handle
+                .store("settings.dat")
+                .ok()
+                .and_then(|s| s.get("segmentSizeEstimateMB"))
+                .and_then(|v| v.as_u64())
+        })
+        .filter(|&mb| mb > 0)
+        .map(|mb| mb * 1024 * 1024)
+        .unwrap_or(DEFAULT_SEGMENT_SIZE_ESTIMATE);
+
+    manager.queue.enqueue(&id);
+    Ok(PreparedDownload {
+        id,
+        url,
+        name,
+        temp_dir,
+        output_dir,
+        thread_count,
+        control,
+        metrics_slot,
+        options,
+    })
+}
+
+/// 等待运行槽位后下载并合并，结束后把任务移出管理器
+async fn run_download(
+    download: PreparedDownload,
+    app_handle: &AppHandle,
+    manager: &DownloadManager,
+) -> Result<(), String> {
+    let PreparedDownload {
+        id,
+        url,
+        name,
+        temp_dir,
+        output_dir,
+        thread_count,
+        control,
+        metrics_slot,
+        options,
+    } = download;
+    let cancelled = control.get_cancel_flag();
+
+    // 达到最大并发数时排队，槽位在下载与合并结束后释放
+    let max_concurrent_tasks = app_handle
+        .store("settings.dat")
+        .ok()
+        .and_then(|s| s.get("downloadCount"))
+        .and_then(|v| v.as_u64())
+        .map(|v| v as usize)
+        .filter(|&count| count > 0)
+        .unwrap_or(DEFAULT_MAX_CONCURRENT_TASKS);
+    let slot = manager
+        .acquire_slot(&id, &control, max_concurrent_tasks, |position| {
+            log::info!("任务 [{}] 排队中，第 {} 位", id, position);
+            let message = format!("等待中，排在第 {} 位", position);
+            let mut update = TaskUpdate::new(&id, TaskPhase::Queued, 1, message.clone());
+            update.details = Some(serde_json::json!({ "queuePosition": position }));
+            emit_task_event(
+                app_handle,
+                "download_queued",
+                serde_json::json!({
+                    "id": id,
+                    "status": 1,
+                    "queuePosition": position,
+                    "message": message,
+                }),
+                update,
+            )
+            .ok();
+        })
+        .await;
+    let Some(_slot) = slot else {
+        // 排队期间被取消或删除，任务已由对应命令移出管理器
+        control.mark_stopped();
+        tray::refresh_tooltip(app_handle).await;
+        return Ok(());
+    };
 
     // 开始下载 TS 文件到临时目录
+    let keep_temp = options.keep_temp;
     let download_result = download_m3u8(
         id.clone(),
         &url,
@@ -72,33 +710,52 @@ pub async fn start_download(
         &temp_dir,
         &output_dir,
         thread_count,
-        cancelled.clone(),
+        control.clone(),
+        metrics_slot,
         app_handle.clone(),
         options,
+        Arc::clone(&manager.clients),
     )
     .await;
+    // 无论成功与否，下载流程都已退出，restart_download 可以安全地接管临时目录
+    control.mark_stopped();
 
     // 下载完成后，从管理器中移除任务
+    // finish_task 只会移除本次登记的任务，不会误伤同 ID 的新任务
     if let Err(e) = &download_result {
         log::error!("{} 下载失败: {}", id, e);
+        emit_task_update(
+            app_handle,
+            TaskUpdate::new(&id, TaskPhase::Error, 0, format!("下载失败: {}", e)),
+        )
+        .ok();
+        notify_task_failed(app_handle, &name, &e.to_string());
         // 下载失败，从管理器移除任务（保留临时目录用于断点续传）
         manager
-            .cancel_task(&id)
+            .finish_task(&id, &control, false)
             .await
             .map_err(|e| format!("取消任务失败: {}", e))?;
+        tray::refresh_tooltip(app_handle).await;
         return Err(e.to_string());
     }
 
     // 检查是否是因为取消而结束的
     // 如果是取消，任务已经从管理器中移除了，不需要再次删除
-    // 如果是正常完成，需要删除临时目录
+    // 如果是正常完成，需要删除临时目录（开启保留临时目录时只移除任务）
     if !cancelled.load(std::sync::atomic::Ordering::Relaxed) {
         // 下载正常完成（未取消），删除任务并清理临时目录
+        if keep_temp {
+            log::info!("任务 [{}] 已保留临时目录: {}", id, temp_dir);
+        }
         manager
-            .delete_task(&id)
+            .finish_task(&id, &control, !keep_temp)
             .await
             .map_err(|e| format!("删除临时目录失败: {}", e))?;
+        if let Err(e) = task_registry::remove_task(app_handle, &id) {
+            log::warn!("任务 [{}] 移除登记失败: {}", id, e);
+        }
     }
+    tray::refresh_tooltip(app_handle).await;
 
     // 根据取消标志输出不同的日志
     if cancelled.load(std::sync::atomic::Ordering::Relaxed) {
@@ -110,6 +767,186 @@ pub async fn start_download(
     Ok(())
 }
 
+/// `batch_download` 命令中的单个任务
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchTaskInput {
+    pub id: String,
+    pub url: String,
+    pub name: String,
+    pub cookies: Option<String>,
+    pub base_url: Option<String>,
+}
+
+/// 批量添加下载任务
+///
+/// 全部任务先按顺序登记并排队，随后在后台依次等待槽位下载，命令立即返回被接受的任务 ID。
+/// 任务 ID 重复或任一任务参数无效时整批拒绝；已在运行中的任务不会被接受。
+#[tauri::command]
+pub async fn batch_download(
+    tasks: Vec<BatchTaskInput>,
+    output_dir: String,
+    thread_count: usize,
+    headers: Option<HashMap<String, String>>,
+    app_handle: AppHandle,
+    manager: tauri::State<'_, DownloadManager>,
+) -> Result<Vec<String>, String> {
+    let mut seen = HashSet::new();
+    if let Some(task) = tasks.iter().find(|t| !seen.insert(t.id.as_str())) {
+        return Err(format!("任务ID重复: {}", task.id));
+    }
+
+    let inputs = tasks
+        .into_iter()
+        .map(|task| {
+            let input = StartDownloadInput {
+                id: task.id,
+                url: task.url,
+                name: task.name,
+                output_dir: output_dir.clone(),
+                thread_count,
+                headers: headers.clone(),
+                cookies: task.cookies,
+                base_url: task.base_url,
+                ..Default::default()
+            };
+            input
+                .validate()
+                .map_err(|e| format!("任务 [{}]: {}", input.id, e))?;
+            Ok(input)
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let mut accepted = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let id = input.id.clone();
+        let download = match register_download(input, &app_handle, &manager).await {
+            Ok(download) => download,
+            Err(e) => {
+                log::error!("任务 [{}] 添加失败: {}", id, e);
+                continue;
+            }
+        };
+        let app_handle = app_handle.clone();
+        tauri::async_runtime::spawn(async move {
+            let manager = app_handle.state::<DownloadManager>();
+            // 失败已通过任务事件通知前端
+            run_download(download, &app_handle, &manager).await.ok();
+        });
+        accepted.push(id);
+    }
+
+    log::info!("批量添加 {} 个下载任务", accepted.len());
+    Ok(accepted)
+}
+
+/// 列出播放列表中的全部轨道（码率变体、音频、字幕），供用户在下载前选择
+///
+/// 地址为分片列表（非主播放列表）时返回 `{"kind": "media"}`
+#[tauri::command]
+pub async fn list_tracks(
+    url: String,
+    base_url: Option<String>,
+    headers: Option<HashMap<String, String>>,
+    app_handle: AppHandle,
+    manager: tauri::State<'_, DownloadManager>,
+) -> Result<PlaylistTracks, String> {
+    log::info!("获取播放列表轨道: {}", url);
+    let client = manager
+        .clients
+        .get(&playlist_request_options(&app_handle))
+        .map_err(|e| e.to_string())?;
+    let base_url = base_url.filter(|b| !b.trim().is_empty());
+    list_playlist_tracks(
+        &client,
+        &url,
+        base_url.as_deref(),
+        headers.unwrap_or_default(),
+    )
+    .await
+    .map_err(|e| format!("获取轨道失败: {}", e))
+}
+
+/// 下载前预览播放列表：是否为主播放列表、分片数、总时长、是否加密
+///
+/// 只请求播放列表本身，不获取密钥也不下载分片
+#[tauri::command]
+pub async fn probe_m3u8(
+    url: String,
+    base_url: Option<String>,
+    headers: Option<HashMap<String, String>>,
+    app_handle: AppHandle,
+    manager: tauri::State<'_, DownloadManager>,
+) -> Result<PlaylistProbe, String> {
+    log::info!("预览播放列表: {}", url);
+    let client = manager
+        .clients
+        .get(&playlist_request_options(&app_handle))
+        .map_err(|e| e.to_string())?;
+    let base_url = base_url.filter(|b| !b.trim().is_empty());
+    probe_playlist(
+        &client,
+        &url,
+        base_url.as_deref(),
+        headers.unwrap_or_default(),
+    )
+    .await
+    .map_err(|e| format!("解析播放列表失败: {}", e))
+}
+
+/// 重新开始下载时等待旧任务停止的最长时间
+const RESTART_STOP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 取消并立即重新开始下载任务
+///
+/// 1. 取消同 ID 的运行中任务，并等待其下载流程完全退出，避免新旧任务同时读写临时目录
+/// 2. `forceFresh` 为 true 时清除临时数据，重新解析播放列表
+/// 3. 使用新的参数（如更新后的请求头）开始下载
+///
+/// 参数与 `start_download` 相同，放在 `input` 中传入。
+#[tauri::command]
+pub async fn restart_download(
+    input: StartDownloadInput,
+    app_handle: AppHandle,
+    manager: tauri::State<'_, DownloadManager>,
+) -> Result<(), String> {
+    input.validate()?;
+    let id = input.id.clone();
+    log::info!(
+        "任务 [{}] 重新开始下载 (force_fresh: {})",
+        id,
+        input.force_fresh
+    );
+
+    if let Some(control) = manager
+        .cancel_task(&id)
+        .await
+        .map_err(|e| e.to_string())?
+    {
+        if !control.wait_stopped(RESTART_STOP_TIMEOUT).await {
+            log::error!("任务 [{}] 旧任务未能在 {:?} 内停止", id, RESTART_STOP_TIMEOUT);
+            return Err("旧任务未能及时停止，请稍后重试".into());
+        }
+        log::info!("任务 [{}] 旧任务已停止", id);
+    }
+
+    let mut update = TaskUpdate::new(&id, TaskPhase::Preparing, 2, "已重新开始下载");
+    update.details = Some(serde_json::json!({ "forceFresh": input.force_fresh }));
+    emit_task_event(
+        &app_handle,
+        "download_restarted",
+        serde_json::json!({
+            "id": id,
+            "forceFresh": input.force_fresh,
+            "message": "已重新开始下载",
+        }),
+        update,
+    )
+    .ok();
+
+    start_download_input(input, &app_handle, &manager).await
+}
+
 /// 取消下载任务
 ///
 /// 1. 取消正在运行的下载任务
@@ -118,13 +955,50 @@ pub async fn start_download(
 #[tauri::command]
 pub async fn cancel_download(
     id: String,
+    app_handle: AppHandle,
     manager: tauri::State<'_, DownloadManager>,
 ) -> Result<(), String> {
     log::info!("取消下载任务: {} (保留临时目录)", id);
     manager.cancel_task(&id).await.map_err(|e| e.to_string())?;
+    tray::refresh_tooltip(&app_handle).await;
     Ok(())
 }
 
+/// 暂停下载任务
+///
+/// 暂停模式读取 settings.dat 中的 `pauseMode`：
+/// - "soft"（默认）：停止领取新分片，保留连接
+/// - "hard"：暂停超过宽限期后释放连接，恢复时重新建立
+#[tauri::command]
+pub async fn pause_download(
+    id: String,
+    app_handle: AppHandle,
+    manager: tauri::State<'_, DownloadManager>,
+) -> Result<(), String> {
+    let mode = app_handle
+        .store("settings.dat")
+        .ok()
+        .and_then(|s| s.get("pauseMode"))
+        .and_then(|v| v.as_str().map(PauseMode::from_setting))
+        .unwrap_or(PauseMode::Soft);
+
+    log::info!("暂停下载任务: {} ({:?})", id, mode);
+    manager
+        .pause_task(&id, mode)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 恢复已暂停的下载任务
+#[tauri::command]
+pub async fn resume_download(
+    id: String,
+    manager: tauri::State<'_, DownloadManager>,
+) -> Result<(), String> {
+    log::info!("恢复下载任务: {}", id);
+    manager.resume_task(&id).await.map_err(|e| e.to_string())
+}
+
 /// 删除下载任务并清理临时目录
 ///
 /// 1. 取消正在运行的任务（如果存在）
@@ -134,33 +1008,97 @@ pub async fn cancel_download(
 pub async fn delete_download(
     id: String,
     output_dir: String,
+    app_handle: AppHandle,
     manager: tauri::State<'_, DownloadManager>,
 ) -> Result<(), String> {
     log::info!("删除下载任务: {}", id);
+    if let Err(e) = task_registry::remove_task(&app_handle, &id) {
+        log::warn!("任务 [{}] 移除登记失败: {}", id, e);
+    }
 
-    // 1. 先检查任务是否在管理器中
-    let task_exists = manager.task_exists(&id).await;
+    // 由管理器统一处理：任务在运行则先取消，再删除临时目录
+    let temp_dir = format!("{}/temp_{}", output_dir, id);
+    manager
+        .delete_task(&id, &temp_dir)
+        .await
+        .map_err(|e| format!("删除任务失败: {}", e))?;
+    tray::refresh_tooltip(&app_handle).await;
 
-    if task_exists {
-        // 任务正在运行，调用 delete_task（会取消并删除临时目录）
-        manager
-            .delete_task(&id)
-            .await
-            .map_err(|e| format!("删除任务失败: {}", e))?;
-    } else {
-        // 任务不在管理器中（已完成或未开始），直接删除临时目录
-        log::info!("任务不在管理器中，直接删除临时目录");
-        let temp_dir = format!("{}/temp_{}", output_dir, id);
-
-        if tokio::fs::try_exists(&temp_dir).await.unwrap_or(false) {
-            tokio::fs::remove_dir_all(&temp_dir)
-                .await
-                .map_err(|e| format!("删除临时目录失败 ({}): {}", temp_dir, e))?;
-            log::info!("已删除临时目录: {}", temp_dir);
+    Ok(())
+}
+
+/// 取消并删除全部任务，同时删除它们的临时目录，返回被删除的任务 ID
+#[tauri::command]
+pub async fn delete_all_tasks(
+    app_handle: AppHandle,
+    manager: tauri::State<'_, DownloadManager>,
+) -> Result<Vec<String>, String> {
+    let ids = manager.delete_all_tasks().await;
+    for id in &ids {
+        if let Err(e) = task_registry::remove_task(&app_handle, id) {
+            log::warn!("任务 [{}] 移除登记失败: {}", id, e);
         }
     }
+    tray::refresh_tooltip(&app_handle).await;
+    Ok(ids)
+}
 
-    Ok(())
+/// 清理输出目录下残留的 `temp_*` 临时目录
+///
+/// 运行中任务和已登记的未完成任务（已暂停或已取消，仍可继续下载）的临时目录会被保留，
+/// 返回删除、保留和失败的目录名称。
+#[tauri::command]
+pub async fn cleanup_temp_dirs(
+    output_dir: String,
+    app_handle: AppHandle,
+    manager: tauri::State<'_, DownloadManager>,
+) -> Result<TempDirCleanup, String> {
+    let resumable = task_registry::registered_ids(&app_handle)
+        .map_err(|e| format!("读取未完成任务失败: {}", e))?;
+    manager
+        .cleanup_temp_dirs(&output_dir, &resumable)
+        .await
+        .map_err(|e| format!("清理临时目录失败: {}", e))
+}
+
+/// 列出所有运行中任务的实时状态
+///
+/// 前端重新打开窗口时用它获取当前快照，之后再依赖事件流更新。
+#[tauri::command]
+pub async fn list_tasks(
+    manager: tauri::State<'_, DownloadManager>,
+) -> Result<Vec<TaskStatus>, String> {
+    Ok(manager.list_tasks().await)
+}
+
+/// 获取单个运行中任务的实时状态，任务不在运行时返回 null
+#[tauri::command]
+pub async fn get_task_status(
+    id: String,
+    manager: tauri::State<'_, DownloadManager>,
+) -> Result<Option<TaskStatus>, String> {
+    Ok(manager.task_status(&id).await)
+}
+
+/// 查找上次运行中未完成、临时目录仍可续传的任务
+///
+/// 正在运行的任务不会返回，前端可将其余任务重新加入列表，由用户决定是否继续下载。
+#[tauri::command]
+pub async fn resume_incomplete_tasks(
+    app_handle: AppHandle,
+    manager: tauri::State<'_, DownloadManager>,
+) -> Result<Vec<IncompleteTask>, String> {
+    let tasks = task_registry::find_incomplete_tasks(&app_handle)
+        .await
+        .map_err(|e| format!("读取未完成任务失败: {}", e))?;
+    let mut incomplete = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        if !manager.task_exists(&task.record.id).await {
+            incomplete.push(task);
+        }
+    }
+    log::info!("找到 {} 个可继续下载的任务", incomplete.len());
+    Ok(incomplete)
 }
 
 /// 获取物理核心数和逻辑线程数
@@ -182,6 +1120,287 @@ pub async fn delete_file(file_path: String) -> Result<(), String> {
     Ok(())
 }
 
+/// 获取日志目录，不存在时先创建
+fn ensure_log_dir(app_handle: &AppHandle) -> Result<std::path::PathBuf, String> {
+    let log_dir = get_log_dir_path(app_handle)?;
+    if !log_dir.exists() {
+        std::fs::create_dir_all(&log_dir).map_err(|e| format!("创建日志目录失败: {}", e))?;
+    }
+    Ok(log_dir)
+}
+
+/// 在系统文件管理器中打开日志目录
+#[tauri::command]
+pub async fn open_log_dir(app_handle: AppHandle) -> Result<(), String> {
+    let log_dir = ensure_log_dir(&app_handle)?;
+    app_handle
+        .opener()
+        .open_path(log_dir.to_string_lossy(), None::<&str>)
+        .map_err(|e| format!("打开日志目录失败: {}", e))?;
+    log::info!("已打开日志目录: {}", log_dir.display());
+    Ok(())
+}
+
+/// 获取日志目录路径，供前端展示
+#[tauri::command]
+pub async fn get_log_dir_path_string(app_handle: AppHandle) -> Result<String, String> {
+    let log_dir = ensure_log_dir(&app_handle)?;
+    Ok(log_dir.to_string_lossy().to_string())
+}
+
+/// 打开下载文件所在的文件夹
+///
+/// `path` 可以是文件夹或已下载的文件，为文件时打开其所在目录。
+#[tauri::command]
+pub async fn open_output_dir(path: String, app_handle: AppHandle) -> Result<(), String> {
+    let path = PathBuf::from(&path);
+    if !path.exists() {
+        return Err(format!(
+            "路径不存在，可能已被移动或删除: {}",
+            path.display()
+        ));
+    }
+    let dir = if path.is_dir() {
+        path.as_path()
+    } else {
+        path.parent()
+            .ok_or_else(|| format!("无法获取所在目录: {}", path.display()))?
+    };
+    app_handle
+        .opener()
+        .open_path(dir.to_string_lossy(), None::<&str>)
+        .map_err(|e| format!("打开文件夹失败: {}", e))?;
+    log::info!("已打开文件夹: {}", dir.display());
+    Ok(())
+}
+
+/// 在系统文件管理器中显示并选中已下载的文件
+///
+/// 不支持选中文件的平台上只打开所在目录。
+#[tauri::command]
+pub async fn reveal_file(path: String, app_handle: AppHandle) -> Result<(), String> {
+    let path = PathBuf::from(&path);
+    if !path.exists() {
+        return Err(format!(
+            "文件不存在，可能已被移动或删除: {}",
+            path.display()
+        ));
+    }
+    app_handle
+        .opener()
+        .reveal_item_in_dir(&path)
+        .map_err(|e| format!("显示文件失败: {}", e))?;
+    log::info!("已在文件管理器中显示: {}", path.display());
+    Ok(())
+}
+
+/// 导出日志时附带的系统信息
+fn system_info(app_handle: &AppHandle) -> String {
+    let sys = System::new();
+    let (physical_cores, logical_cores) = get_cpu_info();
+    let package = app_handle.package_info();
+    format!(
+        "应用版本: {} {}\n操作系统: {} ({} {})\n内核版本: {}\nCPU: {} 物理核心, {} 逻辑线程\n导出时间: {}\n",
+        package.name,
+        package.version,
+        sys.long_os_version().unwrap_or_else(|| "未知".into()),
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        sys.kernel_version().unwrap_or_else(|| "未知".into()),
+        physical_cores,
+        logical_cores,
+        chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+    )
+}
+
+/// 把日志目录打包为 zip 并保存到 `dest_path`（由前端通过保存对话框选择），用于反馈问题
+///
+/// 压缩包包含全部日志（含滚动和已压缩的日志）以及 system_info.txt。
+#[tauri::command]
+pub async fn export_logs(dest_path: String, app_handle: AppHandle) -> Result<(), String> {
+    if dest_path.trim().is_empty() {
+        return Err("保存路径不能为空".into());
+    }
+    let log_dir = ensure_log_dir(&app_handle)?;
+    let dest = PathBuf::from(dest_path.trim());
+    let info = system_info(&app_handle);
+
+    let count = {
+        let dest = dest.clone();
+        tokio::task::spawn_blocking(move || logger::export::export_logs(&log_dir, &dest, &info))
+            .await
+            .map_err(|e| format!("导出日志失败: {}", e))?
+            .map_err(|e| format!("导出日志失败: {}", e))?
+    };
+    log::info!("已导出 {} 个日志文件到: {}", count, dest.display());
+    Ok(())
+}
+
+/// 校验已下载的分片（不下载、不合并）
+///
+/// `depth` 为 "exists" 时只检查文件存在且非空，为 "full" 时额外检查 TS 同步字节
+#[tauri::command]
+pub async fn verify_download(
+    id: String,
+    output_dir: String,
+    depth: Option<VerifyDepth>,
+) -> Result<VerifyReport, String> {
+    let temp_dir = format!("{}/temp_{}", output_dir, id);
+    let depth = depth.unwrap_or_default();
+    let report = verify_segments(&temp_dir, depth)
+        .await
+        .map_err(|e| format!("校验分片失败: {}", e))?;
+    log::info!(
+        "任务 [{}] 分片校验({:?}): 共 {}, 正常 {}, 缺失 {}, 无效 {}",
+        id,
+        depth,
+        report.total,
+        report.present,
+        report.missing.len(),
+        report.invalid.len()
+    );
+    Ok(report)
+}
+
+/// 直接合并临时目录中已下载完成的分片（不下载）
+///
+/// 读取 `temp_dir` 中的 segments.json，所有分片文件存在且非空时按播放列表顺序合并。
+/// 合并成功后保留临时目录，由用户自行决定是否删除。
+#[tauri::command]
+pub async fn merge_existing(
+    id: String,
+    name: String,
+    temp_dir: String,
+    output_dir: String,
+    app_handle: AppHandle,
+    manager: tauri::State<'_, DownloadManager>,
+) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("文件名不能为空".into());
+    }
+    if !PathBuf::from(&temp_dir).join("segments.json").is_file() {
+        return Err(format!(
+            "临时目录中没有分片元数据 segments.json: {}",
+            temp_dir
+        ));
+    }
+
+    let task = DownloadTask::new(name.clone(), temp_dir.clone());
+    let control = Arc::clone(&task.control);
+    manager
+        .add_task(id.clone(), task)
+        .await
+        .map_err(|e| format!("添加合并任务失败: {}", e))?;
+    tray::refresh_tooltip(&app_handle).await;
+
+    let mut options = DownloadOptions::new();
+    if let Some(auto_reencode) = auto_reencode_setting(&app_handle) {
+        options.auto_reencode = auto_reencode;
+    }
+    if let Some(timestamp_mode) = timestamp_mode_setting(&app_handle) {
+        options.timestamp_mode = timestamp_mode;
+    }
+    options.output_format = app_handle
+        .store("settings.dat")
+        .ok()
+        .and_then(|s| s.get("outputFormat"))
+        .and_then(|v| v.as_str().map(|s| s.to_string()))
+        .and_then(|v| OutputFormat::try_from(v).ok())
+        .unwrap_or_default();
+    options.ffmpeg = FfmpegSource::from_settings(&app_handle);
+    options.overwrite = app_handle
+        .store("settings.dat")
+        .ok()
+        .and_then(|s| s.get("overwriteExisting"))
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    options.generate_thumbnail = app_handle
+        .store("settings.dat")
+        .ok()
+        .and_then(|s| s.get("generateThumbnail"))
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+
+    log::info!("任务 [{}] 直接合并临时目录: {}", id, temp_dir);
+    let result = merge_existing_segments(
+        id.clone(),
+        &name,
+        &temp_dir,
+        &output_dir,
+        control.clone(),
+        app_handle.clone(),
+        options,
+    )
+    .await;
+    control.mark_stopped();
+    manager
+        .finish_task(&id, &control, false)
+        .await
+        .map_err(|e| format!("移除合并任务失败: {}", e))?;
+    tray::refresh_tooltip(&app_handle).await;
+
+    if let Err(e) = result {
+        log::error!("任务 [{}] 直接合并失败: {}", id, e);
+        emit_task_update(
+            &app_handle,
+            TaskUpdate::new(&id, TaskPhase::Error, 0, format!("合并失败: {}", e)),
+        )
+        .ok();
+        notify_task_failed(&app_handle, &name, &e.to_string());
+        return Err(format!("合并失败: {}", e));
+    }
+    if let Err(e) = task_registry::remove_task(&app_handle, &id) {
+        log::warn!("任务 [{}] 移除登记失败: {}", id, e);
+    }
+    Ok(())
+}
+
+/// 运行环境自检
+///
+/// 检查 ffmpeg、日志目录、设置存储、临时目录，`network_url` 不为 None 时额外检查网络
+/// （传空字符串使用默认地址）。网络检查失败不影响整体结果。
+#[tauri::command]
+pub async fn self_test(
+    output_dir: Option<String>,
+    network_url: Option<String>,
+    app_handle: AppHandle,
+) -> Result<SelfTestReport, String> {
+    log::info!("开始运行环境自检");
+    Ok(run_self_test(&app_handle, output_dir, network_url).await)
+}
+
+/// 获取累计下载流量（全部、本次运行、按天）
+#[tauri::command]
+pub async fn get_data_usage(app_handle: AppHandle) -> Result<DataUsage, String> {
+    data_usage::get_data_usage(&app_handle).map_err(|e| format!("读取流量统计失败: {}", e))
+}
+
+/// 清空流量统计
+#[tauri::command]
+pub async fn reset_data_usage(app_handle: AppHandle) -> Result<(), String> {
+    data_usage::reset_data_usage(&app_handle).map_err(|e| format!("重置流量统计失败: {}", e))?;
+    log::info!("流量统计已重置");
+    Ok(())
+}
+
+/// 运行时修改日志级别，同时写入 settings.dat，下次启动沿用
+#[tauri::command]
+pub async fn set_log_level(level: String, app_handle: AppHandle) -> Result<(), String> {
+    let level_filter = level
+        .parse::<LevelFilter>()
+        .map_err(|_| format!("无效的日志级别: {}", level))?;
+    logger::set_log_level(level_filter);
+
+    let store = app_handle
+        .store("settings.dat")
+        .map_err(|e| format!("加载Store失败: {}", e))?;
+    store.set("logLevel", level);
+    store
+        .save()
+        .map_err(|e| format!("保存Store配置失败: {}", e))?;
+    Ok(())
+}
+
 /// 将设置项保存到 settings.dat
 #[tauri::command]
 pub async fn save_settings(settings_object: Value, app_handle: AppHandle) -> Result<(), String> {
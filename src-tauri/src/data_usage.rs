@@ -0,0 +1,91 @@
+//! 流量统计模块
+//! 在 usage.dat 中累计记录应用下载的字节数，供按流量计费的用户参考。
+//! 仅在任务结束时更新一次，避免频繁写盘。
+
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tauri::AppHandle;
+use tauri_plugin_store::StoreExt;
+
+const USAGE_STORE: &str = "usage.dat";
+const TOTAL_BYTES_KEY: &str = "totalBytes";
+const DAILY_BYTES_KEY: &str = "dailyBytes";
+/// 按天统计最多保留的天数
+const MAX_DAILY_ENTRIES: usize = 90;
+
+/// 本次运行期间下载的字节数
+static SESSION_BYTES: AtomicU64 = AtomicU64::new(0);
+/// 串行化 usage.dat 的读-改-写，避免多个任务同时结束时互相覆盖
+static USAGE_LOCK: Mutex<()> = Mutex::new(());
+
+/// 流量统计结果
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DataUsage {
+    pub total_bytes: u64,
+    pub session_bytes: u64,
+    /// 日期 (YYYY-MM-DD) -> 字节数
+    pub daily_bytes: BTreeMap<String, u64>,
+}
+
+fn parse_daily(value: Option<serde_json::Value>) -> BTreeMap<String, u64> {
+    value
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+/// 将一个任务本次运行实际下载的字节数计入统计并立即保存
+pub fn record_data_usage(app_handle: &AppHandle, bytes: u64) -> Result<()> {
+    if bytes == 0 {
+        return Ok(());
+    }
+    SESSION_BYTES.fetch_add(bytes, Ordering::Relaxed);
+
+    let _guard = USAGE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let store = app_handle.store(USAGE_STORE)?;
+
+    let total = store
+        .get(TOTAL_BYTES_KEY)
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0)
+        .saturating_add(bytes);
+
+    let mut daily = parse_daily(store.get(DAILY_BYTES_KEY));
+    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+    *daily.entry(today).or_insert(0) += bytes;
+    // 日期字符串按字典序即按时间排序，移除最早的记录
+    while daily.len() > MAX_DAILY_ENTRIES {
+        daily.pop_first();
+    }
+
+    store.set(TOTAL_BYTES_KEY, total);
+    store.set(DAILY_BYTES_KEY, serde_json::to_value(&daily)?);
+    store.save()?;
+    Ok(())
+}
+
+/// 读取流量统计
+pub fn get_data_usage(app_handle: &AppHandle) -> Result<DataUsage> {
+    let store = app_handle.store(USAGE_STORE)?;
+    Ok(DataUsage {
+        total_bytes: store
+            .get(TOTAL_BYTES_KEY)
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0),
+        session_bytes: SESSION_BYTES.load(Ordering::Relaxed),
+        daily_bytes: parse_daily(store.get(DAILY_BYTES_KEY)),
+    })
+}
+
+/// 清空流量统计（含本次运行的计数）
+pub fn reset_data_usage(app_handle: &AppHandle) -> Result<()> {
+    let _guard = USAGE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let store = app_handle.store(USAGE_STORE)?;
+    store.clear();
+    store.save()?;
+    SESSION_BYTES.store(0, Ordering::Relaxed);
+    Ok(())
+}
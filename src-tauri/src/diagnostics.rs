@@ -0,0 +1,256 @@
+//! 诊断模块
+//! 一次性检查运行环境（ffmpeg、日志目录、设置存储、网络、临时目录、AES 硬件加速），
+//! 用于排查安装问题。
+
+use crate::logger::rotate::get_log_dir_path;
+use crate::merge::{create_ffmpeg_command, resolve_ffmpeg, FfmpegSource};
+use aes::Aes128;
+use cbc::Decryptor;
+use cipher::{block_padding::NoPadding, BlockDecryptMut, KeyIvInit};
+use serde::Serialize;
+use std::time::{Duration, Instant};
+use tauri::AppHandle;
+use tauri_plugin_store::StoreExt;
+
+/// 网络检查默认请求的地址（应用更新地址）
+const DEFAULT_NETWORK_CHECK_URL: &str =
+    "https://github.com/Colzry/m3u8-downloader/releases/latest/download/latest.json";
+/// 网络检查超时
+const NETWORK_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+/// 解密测速使用的数据量
+const DECRYPT_BENCH_BYTES: usize = 8 * 1024 * 1024;
+
+/// 单项检查结果
+#[derive(Debug, Clone, Serialize)]
+pub struct SelfTestCheck {
+    pub name: &'static str,
+    pub passed: bool,
+    /// 为 false 时该项失败不影响整体结果（如离线环境下的网络检查）
+    pub required: bool,
+    pub message: String,
+}
+
+/// 自检报告
+#[derive(Debug, Clone, Serialize)]
+pub struct SelfTestReport {
+    pub passed: bool,
+    pub checks: Vec<SelfTestCheck>,
+}
+
+impl SelfTestCheck {
+    fn from_result(name: &'static str, required: bool, result: Result<String, String>) -> Self {
+        let (passed, message) = match result {
+            Ok(msg) => (true, msg),
+            Err(msg) => (false, msg),
+        };
+        Self {
+            name,
+            passed,
+            required,
+            message,
+        }
+    }
+}
+
+/// ffmpeg 能否解析并运行
+async fn check_ffmpeg(app_handle: &AppHandle) -> Result<String, String> {
+    let ffmpeg_path = resolve_ffmpeg(app_handle, &FfmpegSource::from_settings(app_handle))
+        .await
+        .map_err(|e| e.to_string())?;
+    let ffmpeg = ffmpeg_path.to_str().ok_or("ffmpeg 路径无效")?;
+
+    let output = create_ffmpeg_command(ffmpeg)
+        .arg("-version")
+        .output()
+        .await
+        .map_err(|e| format!("无法运行 ffmpeg ({}): {}", ffmpeg, e))?;
+    if !output.status.success() {
+        return Err(format!("ffmpeg 运行失败，退出状态: {}", output.status));
+    }
+
+    // 第一行形如 "ffmpeg version 7.0 Copyright ..."
+    let version = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .to_string();
+    Ok(version)
+}
+
+/// 日志目录是否可写
+async fn check_log_dir(app_handle: &AppHandle) -> Result<String, String> {
+    let log_dir = get_log_dir_path(app_handle)?;
+    tokio::fs::create_dir_all(&log_dir)
+        .await
+        .map_err(|e| format!("创建日志目录失败: {}", e))?;
+
+    let probe = log_dir.join(".self_test");
+    tokio::fs::write(&probe, b"ok")
+        .await
+        .map_err(|e| format!("日志目录不可写 ({}): {}", log_dir.display(), e))?;
+    tokio::fs::remove_file(&probe).await.ok();
+    Ok(log_dir.display().to_string())
+}
+
+/// 设置存储能否加载
+fn check_settings_store(app_handle: &AppHandle) -> Result<String, String> {
+    let store = app_handle
+        .store("settings.dat")
+        .map_err(|e| format!("加载 settings.dat 失败: {}", e))?;
+    store
+        .reload()
+        .map_err(|e| format!("读取 settings.dat 失败: {}", e))?;
+    Ok("settings.dat 加载正常".into())
+}
+
+/// CPU 是否支持 AES 指令，以及当前构建是否会使用它
+///
+/// aes crate 在 x86/x86_64 上运行时自动检测 AES-NI；aarch64 需在编译时开启 `aes_armv8`。
+fn aes_hardware_support() -> (bool, &'static str) {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if std::is_x86_feature_detected!("aes") {
+            (true, "AES-NI")
+        } else {
+            (false, "CPU 不支持 AES-NI，使用软件实现")
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        if !std::arch::is_aarch64_feature_detected!("aes") {
+            (false, "CPU 不支持 ARMv8 AES 指令，使用软件实现")
+        } else if cfg!(aes_armv8) {
+            (true, "ARMv8 AES")
+        } else {
+            (false, "当前构建未开启 aes_armv8，使用软件实现")
+        }
+    }
+    #[cfg(not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")))]
+    {
+        (false, "当前平台无 AES 硬件加速，使用软件实现")
+    }
+}
+
+/// AES-128-CBC 解密是否使用硬件加速，并测量解密吞吐量
+async fn check_aes() -> Result<String, String> {
+    let (hardware, description) = aes_hardware_support();
+
+    let elapsed = tokio::task::spawn_blocking(|| {
+        let key = [0u8; 16];
+        let iv = [0u8; 16];
+        let mut buffer = vec![0u8; DECRYPT_BENCH_BYTES];
+        let start = Instant::now();
+        Decryptor::<Aes128>::new(&key.into(), &iv.into())
+            .decrypt_padded_mut::<NoPadding>(&mut buffer)
+            .map(|_| start.elapsed())
+            .map_err(|e| format!("解密测试失败: {:?}", e))
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    let throughput = DECRYPT_BENCH_BYTES as f64 / 1024.0 / 1024.0 / elapsed.as_secs_f64();
+    let message = format!("{}，解密速度 {:.0} MB/s", description, throughput);
+    if hardware {
+        Ok(message)
+    } else {
+        Err(message)
+    }
+}
+
+/// 能否访问外网
+async fn check_network(url: &str) -> Result<String, String> {
+    let client = reqwest::Client::builder()
+        .timeout(NETWORK_CHECK_TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| format!("请求 {} 失败: {}", url, e))?;
+    let status = response.status();
+    if status.is_success() {
+        Ok(format!("{} -> {}", url, status))
+    } else {
+        Err(format!("{} 返回状态码 {}", url, status))
+    }
+}
+
+/// 能否在下载目录中创建和删除临时目录
+async fn check_temp_dir(output_dir: Option<&str>) -> Result<String, String> {
+    let output_dir = output_dir
+        .filter(|d| !d.trim().is_empty())
+        .ok_or("未设置下载目录")?;
+    let temp_dir = format!(
+        "{}/temp_self_test_{}",
+        output_dir,
+        chrono::Local::now().format("%Y%m%d%H%M%S")
+    );
+
+    tokio::fs::create_dir_all(&temp_dir)
+        .await
+        .map_err(|e| format!("创建临时目录失败 ({}): {}", temp_dir, e))?;
+    let write_result = tokio::fs::write(format!("{}/part_0.ts", temp_dir), b"ok").await;
+    let remove_result = tokio::fs::remove_dir_all(&temp_dir).await;
+
+    write_result.map_err(|e| format!("临时目录不可写 ({}): {}", temp_dir, e))?;
+    remove_result.map_err(|e| format!("删除临时目录失败 ({}): {}", temp_dir, e))?;
+    Ok(format!("{} 可正常创建和删除临时目录", output_dir))
+}
+
+/// 运行全部自检项
+///
+/// `output_dir` 为空时使用 settings.dat 中的 `downloadPath`；
+/// `network_url` 为 None 时跳过网络检查，网络检查失败不影响整体结果。
+pub async fn run_self_test(
+    app_handle: &AppHandle,
+    output_dir: Option<String>,
+    network_url: Option<String>,
+) -> SelfTestReport {
+    let output_dir = output_dir.or_else(|| {
+        app_handle
+            .store("settings.dat")
+            .ok()
+            .and_then(|s| s.get("downloadPath"))
+            .and_then(|v| v.as_str().map(|s| s.to_string()))
+    });
+
+    let mut checks = vec![
+        SelfTestCheck::from_result("ffmpeg", true, check_ffmpeg(app_handle).await),
+        SelfTestCheck::from_result("log_dir", true, check_log_dir(app_handle).await),
+        SelfTestCheck::from_result("settings_store", true, check_settings_store(app_handle)),
+        SelfTestCheck::from_result(
+            "temp_dir",
+            true,
+            check_temp_dir(output_dir.as_deref()).await,
+        ),
+        // 软件实现只影响速度，不影响功能
+        SelfTestCheck::from_result("aes_hardware", false, check_aes().await),
+    ];
+
+    if let Some(url) = network_url {
+        let url = if url.trim().is_empty() {
+            DEFAULT_NETWORK_CHECK_URL.to_string()
+        } else {
+            url
+        };
+        checks.push(SelfTestCheck::from_result(
+            "network",
+            false,
+            check_network(&url).await,
+        ));
+    }
+
+    for check in &checks {
+        if check.passed {
+            log::info!("自检 [{}] 通过: {}", check.name, check.message);
+        } else {
+            log::warn!("自检 [{}] 失败: {}", check.name, check.message);
+        }
+    }
+
+    SelfTestReport {
+        passed: checks.iter().all(|c| c.passed || !c.required),
+        checks,
+    }
+}
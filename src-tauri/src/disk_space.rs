@@ -0,0 +1,175 @@
+//! 磁盘空间检查模块
+//! 下载前按估算大小检查保存目录所在磁盘的剩余空间，下载过程中定期复查，
+//! 空间不足时暂停任务，避免分片写入失败后只留下难以理解的 I/O 错误。
+
+use crate::download_manager::{DownloadControl, PauseMode};
+use crate::task_events::{emit_task_event, TaskPhase, TaskUpdate};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use sysinfo::{DiskExt, System, SystemExt};
+use tauri::AppHandle;
+
+/// 无法获取分片大小时，每个分片按该大小估算（字节）
+pub const DEFAULT_SEGMENT_SIZE_ESTIMATE: u64 = 2 * 1024 * 1024;
+/// 下载过程中剩余空间低于该值时暂停任务（字节）
+pub const DEFAULT_MIN_FREE_SPACE: u64 = 512 * 1024 * 1024;
+/// 下载过程中复查剩余空间的间隔
+const DISK_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// 空间不足事件
+const DISK_SPACE_EVENT: &str = "disk_space_low";
+
+/// 去掉 Windows 规范化路径的 `\\?\` 前缀，便于和挂载点比较
+fn normalize(path: PathBuf) -> PathBuf {
+    match path.to_str().and_then(|p| p.strip_prefix(r"\\?\")) {
+        Some(stripped) => PathBuf::from(stripped),
+        None => path,
+    }
+}
+
+/// 获取路径所在磁盘的剩余空间（字节），无法确定所在磁盘时返回 None
+pub fn available_space(path: &Path) -> Option<u64> {
+    let path = normalize(std::fs::canonicalize(path).ok()?);
+    let mut sys = System::new();
+    sys.refresh_disks_list();
+    // 挂载点可能嵌套，取与路径匹配的最长挂载点
+    sys.disks()
+        .iter()
+        .filter(|disk| path.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        .map(|disk| disk.available_space())
+}
+
+/// 格式化字节数，如 "1.50 GB"
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.2} {}", value, UNITS[unit])
+}
+
+/// 下载前检查剩余空间
+///
+/// `required` 为待下载分片加上合并输出文件的估算大小；无法获取剩余空间时不做限制。
+/// 枚举磁盘是阻塞操作，放到阻塞线程中执行。
+pub async fn check_free_space(
+    app_handle: &AppHandle,
+    id: &str,
+    output_dir: &str,
+    required: u64,
+) -> anyhow::Result<()> {
+    let dir = output_dir.to_string();
+    let available = tokio::task::spawn_blocking(move || available_space(Path::new(&dir)))
+        .await
+        .ok()
+        .flatten();
+    let Some(available) = available else {
+        log::warn!(
+            "任务 [{}] 无法获取 {} 所在磁盘的剩余空间，跳过检查",
+            id,
+            output_dir
+        );
+        return Ok(());
+    };
+    log::info!(
+        "任务 [{}] 预计需要 {}，剩余 {}",
+        id,
+        format_bytes(required),
+        format_bytes(available)
+    );
+    if available >= required {
+        return Ok(());
+    }
+
+    let message = format!(
+        "磁盘空间不足：预计需要 {}，保存目录所在磁盘仅剩 {}",
+        format_bytes(required),
+        format_bytes(available)
+    );
+    emit_task_event(
+        app_handle,
+        DISK_SPACE_EVENT,
+        serde_json::json!({
+            "id": id,
+            "message": message,
+            "requiredBytes": required,
+            "availableBytes": available,
+            "paused": false,
+        }),
+        TaskUpdate::new(id, TaskPhase::Error, 0, message.clone()),
+    )
+    .ok();
+    Err(anyhow::anyhow!(message))
+}
+
+/// 下载过程中定期复查剩余空间，低于 `min_free_space` 时暂停任务并提示
+///
+/// 任务已暂停时不再检查；用户清理空间后手动恢复，空间仍不足会再次暂停。
+pub async fn watch_free_space(
+    id: String,
+    output_dir: String,
+    min_free_space: u64,
+    control: Arc<DownloadControl>,
+    app_handle: AppHandle,
+) {
+    let mut interval = tokio::time::interval(DISK_CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        if control.is_cancelled() {
+            return;
+        }
+        if control.is_paused() {
+            continue;
+        }
+        let dir = output_dir.clone();
+        let available = tokio::task::spawn_blocking(move || available_space(Path::new(&dir)))
+            .await
+            .ok()
+            .flatten();
+        let Some(available) = available.filter(|&a| a < min_free_space) else {
+            continue;
+        };
+
+        control.pause(PauseMode::Soft);
+        let message = format!(
+            "磁盘剩余空间仅 {}，已暂停下载，请清理后继续",
+            format_bytes(available)
+        );
+        log::warn!("任务 [{}] {}", id, message);
+        emit_task_event(
+            &app_handle,
+            DISK_SPACE_EVENT,
+            serde_json::json!({
+                "id": id,
+                "message": message,
+                "availableBytes": available,
+                "paused": true,
+            }),
+            TaskUpdate::new(&id, TaskPhase::Paused, 6, message.clone()),
+        )
+        .ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_bytes_boundaries() {
+        assert_eq!(format_bytes(0), "0.00 B");
+        assert_eq!(format_bytes(1023), "1023.00 B");
+        assert_eq!(format_bytes(1024), "1.00 KB");
+        assert_eq!(format_bytes(1536), "1.50 KB");
+        assert_eq!(format_bytes(45_200 * 1024), "44.14 MB");
+        assert_eq!(format_bytes(1024 * 1024 * 1024), "1.00 GB");
+        assert_eq!(format_bytes(3 * 1024 * 1024 * 1024 / 2), "1.50 GB");
+        // GB 是最大单位
+        assert_eq!(format_bytes(2 * 1024u64.pow(4)), "2048.00 GB");
+    }
+}
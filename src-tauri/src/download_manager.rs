@@ -1,37 +1,324 @@
+use crate::download::SharedClients;
+use crate::download_monitor::DownloadMetrics;
+use crate::task_events::TaskPhase;
+use serde::Serialize;
+use std::collections::{HashSet, VecDeque};
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
 use std::{collections::HashMap, sync::Arc};
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, Notify};
+
+/// 暂停模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseMode {
+    /// 软暂停：只停止领取新分片，保留已建立的连接
+    Soft,
+    /// 硬暂停：暂停超过宽限期后不再持有共享连接池，恢复时重新建立连接
+    Hard,
+}
+
+impl PauseMode {
+    /// 从设置值解析暂停模式，无法识别时使用软暂停
+    pub fn from_setting(value: &str) -> Self {
+        match value.trim().to_lowercase().as_str() {
+            "hard" => PauseMode::Hard,
+            _ => PauseMode::Soft,
+        }
+    }
+}
+
+/// 下载任务的实时控制器
+///
+/// 由 DownloadTask 持有，并以 `Arc` 形式传入下载流程，
+/// 工作协程通过它感知取消与暂停。
+pub struct DownloadControl {
+    cancelled: Arc<AtomicBool>,
+    paused: AtomicBool,
+    hard_pause: AtomicBool,
+    // 暂停、恢复、取消时都会通知等待者
+    pause_notify: Notify,
+    // 下载流程是否已经退出（不再读写临时目录）
+    stopped: AtomicBool,
+    stopped_notify: Notify,
+}
+
+impl DownloadControl {
+    pub fn new() -> Self {
+        Self {
+            cancelled: Arc::new(AtomicBool::new(false)),
+            paused: AtomicBool::new(false),
+            hard_pause: AtomicBool::new(false),
+            pause_notify: Notify::new(),
+            stopped: AtomicBool::new(false),
+            stopped_notify: Notify::new(),
+        }
+    }
+
+    /// 标记下载流程已退出，唤醒等待任务停止的协程
+    pub fn mark_stopped(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        self.stopped_notify.notify_waiters();
+    }
+
+    /// 等待下载流程退出，超时返回 false
+    pub async fn wait_stopped(&self, timeout: Duration) -> bool {
+        tokio::time::timeout(timeout, async {
+            loop {
+                let notified = self.stopped_notify.notified();
+                if self.stopped.load(Ordering::SeqCst) {
+                    return;
+                }
+                notified.await;
+            }
+        })
+        .await
+        .is_ok()
+    }
+
+    /// 取消下载，同时唤醒所有因暂停而等待的协程
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.pause_notify.notify_waiters();
+    }
+
+    /// 检查取消状态
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// 获取取消标志的克隆
+    pub fn get_cancel_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.cancelled)
+    }
+
+    /// 暂停下载
+    pub fn pause(&self, mode: PauseMode) {
+        self.hard_pause.store(mode == PauseMode::Hard, Ordering::SeqCst);
+        self.paused.store(true, Ordering::SeqCst);
+        self.pause_notify.notify_waiters();
+    }
+
+    /// 恢复下载
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+        self.pause_notify.notify_waiters();
+    }
+
+    /// 检查暂停状态
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// 当前暂停模式
+    pub fn pause_mode(&self) -> PauseMode {
+        if self.hard_pause.load(Ordering::SeqCst) {
+            PauseMode::Hard
+        } else {
+            PauseMode::Soft
+        }
+    }
+
+    /// 获取暂停状态变化的通知器
+    pub fn get_notify(&self) -> &Notify {
+        &self.pause_notify
+    }
+
+    /// 挂起当前协程，直到任务被取消
+    pub async fn cancelled(&self) {
+        loop {
+            let notified = self.pause_notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// 挂起当前协程，直到任务被暂停或取消
+    pub async fn paused_or_cancelled(&self) {
+        loop {
+            let notified = self.pause_notify.notified();
+            if self.is_paused() || self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// 暂停时挂起当前协程，直到恢复或取消
+    pub async fn wait_if_paused(&self) {
+        loop {
+            // 先注册等待再检查状态，避免错过 notify_waiters 的唤醒
+            let notified = self.pause_notify.notified();
+            if !self.is_paused() || self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+/// 任务的下载统计
+///
+/// 解析完播放列表、确定分片数量后由下载流程写入，此前为空。
+pub type MetricsSlot = Arc<OnceLock<Arc<DownloadMetrics>>>;
+
+/// 任务的实时状态快照
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskStatus {
+    pub id: String,
+    pub name: String,
+    pub phase: TaskPhase,
+    /// 与前端一致的状态码，见 [`TaskPhase`]
+    pub status: u32,
+    /// 按分片计算的下载进度百分比（0-100）
+    pub progress: u32,
+    pub paused: bool,
+}
+
+/// `cleanup_temp_dirs` 的清理结果，均为临时目录的名称（`temp_{id}`）
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TempDirCleanup {
+    /// 已删除的目录
+    pub removed: Vec<String>,
+    /// 属于运行中任务而保留的目录
+    pub active: Vec<String>,
+    /// 属于未完成、仍可继续下载的任务（已暂停或已取消）而保留的目录
+    pub resumable: Vec<String>,
+    /// 删除失败的目录
+    pub failed: Vec<String>,
+}
 
 /// 运行时下载任务的句柄
 ///
-/// 存储在 DownloadManager 中，用于关联一个 ID 和它的实时控制器。
+/// 存储在 DownloadManager 中，用于关联一个 ID 和它的实时控制器、下载统计。
 pub struct DownloadTask {
-    pub cancelled: Arc<AtomicBool>,
+    pub control: Arc<DownloadControl>,
     pub temp_dir: String,
+    pub name: String,
+    pub metrics: MetricsSlot,
     // 如果需要，还可以保存下载任务的 JoinHandle
 }
 
 impl DownloadTask {
-    pub fn new(temp_dir: String) -> Self {
+    pub fn new(name: String, temp_dir: String) -> Self {
         Self {
-            cancelled: Arc::new(AtomicBool::new(false)),
+            control: Arc::new(DownloadControl::new()),
             temp_dir,
+            name,
+            metrics: MetricsSlot::default(),
+        }
+    }
+
+    /// 获取下载统计槽位的克隆，供下载流程写入
+    pub fn metrics_slot(&self) -> MetricsSlot {
+        Arc::clone(&self.metrics)
+    }
+
+    /// 生成状态快照
+    ///
+    /// 排队中的任务状态为等待中；播放列表尚未解析完时处于准备阶段；分片全部结束后视为已下载，等待或正在合并。
+    pub fn status(&self, id: &str, queued: bool) -> TaskStatus {
+        let paused = self.control.is_paused();
+        let metrics = self.metrics.get();
+        let progress = metrics
+            .map(|m| m.get_progress().round() as u32)
+            .unwrap_or(0);
+        let (phase, status) = match metrics {
+            _ if self.control.is_cancelled() => (TaskPhase::Cancelled, 0),
+            _ if queued => (TaskPhase::Queued, 1),
+            Some(m) if m.is_downloaded() => (TaskPhase::Downloaded, 3),
+            _ if paused => (TaskPhase::Paused, 6),
+            Some(_) => (TaskPhase::Downloading, 2),
+            None => (TaskPhase::Preparing, 2),
+        };
+        TaskStatus {
+            id: id.to_string(),
+            name: self.name.clone(),
+            phase,
+            status,
+            progress,
+            paused,
         }
     }
 
     /// 取消下载
     pub fn cancel(&self) {
-        self.cancelled.store(true, Ordering::SeqCst);
+        self.control.cancel();
     }
 
     /// 检查取消状态
     pub fn is_cancelled(&self) -> bool {
-        self.cancelled.load(Ordering::SeqCst)
+        self.control.is_cancelled()
     }
 
-    /// 获取取消标志的克隆
-    pub fn get_cancel_flag(&self) -> Arc<AtomicBool> {
-        Arc::clone(&self.cancelled)
+    /// 获取控制器的克隆
+    pub fn get_control(&self) -> Arc<DownloadControl> {
+        Arc::clone(&self.control)
+    }
+}
+
+/// 未设置时同时下载的任务数
+pub const DEFAULT_MAX_CONCURRENT_TASKS: usize = 1;
+
+#[derive(Default)]
+struct QueueState {
+    /// 等待中的任务 ID，按排队顺序
+    waiting: VecDeque<String>,
+    /// 已占用槽位的任务数
+    running: usize,
+}
+
+/// 任务排队
+///
+/// 超过最大并发数的任务按先后顺序等待，槽位释放后队首任务自动开始。
+#[derive(Default)]
+pub struct TaskQueue {
+    state: std::sync::Mutex<QueueState>,
+    notify: Notify,
+}
+
+impl TaskQueue {
+    fn state(&self) -> std::sync::MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 任务在队列中的位置（从 1 开始），不在排队时返回 None
+    pub fn position(&self, id: &str) -> Option<usize> {
+        self.state()
+            .waiting
+            .iter()
+            .position(|w| w == id)
+            .map(|i| i + 1)
+    }
+
+    /// 把任务加入队尾，已在队列中时保持原位置
+    ///
+    /// 登记任务时调用，保证批量添加的任务按添加顺序获得槽位。
+    pub fn enqueue(&self, id: &str) {
+        let mut state = self.state();
+        if !state.waiting.iter().any(|w| w == id) {
+            state.waiting.push_back(id.to_string());
+        }
+    }
+
+    /// 从队列中移除任务并唤醒其余等待者
+    fn leave(&self, id: &str) {
+        self.state().waiting.retain(|w| w != id);
+        self.notify.notify_waiters();
+    }
+}
+
+/// 运行槽位，离开作用域时释放并唤醒排队的任务
+pub struct TaskSlot(Arc<TaskQueue>);
+
+impl Drop for TaskSlot {
+    fn drop(&mut self) {
+        let mut state = self.0.state();
+        state.running = state.running.saturating_sub(1);
+        drop(state);
+        self.0.notify.notify_waiters();
     }
 }
 
@@ -44,60 +331,231 @@ impl DownloadTask {
 /// 2. 响应Tauri命令，对 *正在运行* 的任务进行操作（取消并删除）。
 pub struct DownloadManager {
     pub tasks: Mutex<HashMap<String, DownloadTask>>,
+    /// 所有任务共用的 HTTP 客户端
+    pub clients: Arc<SharedClients>,
+    /// 等待运行槽位的任务
+    pub queue: Arc<TaskQueue>,
 }
 
 impl DownloadManager {
     pub fn new() -> Self {
         Self {
             tasks: Mutex::new(HashMap::new()),
+            clients: Arc::default(),
+            queue: Arc::new(TaskQueue::default()),
+        }
+    }
+
+    /// 等待运行槽位
+    ///
+    /// 正在运行的任务达到 `max_concurrent_tasks` 时排队，位置变化时调用 `on_position`。
+    /// 排队期间任务被取消或删除则离开队列并返回 None。
+    pub async fn acquire_slot(
+        &self,
+        id: &str,
+        control: &DownloadControl,
+        max_concurrent_tasks: usize,
+        mut on_position: impl FnMut(usize),
+    ) -> Option<TaskSlot> {
+        let queue = &self.queue;
+        let mut last_position = None;
+        loop {
+            // 先注册等待再检查状态，避免错过 notify_waiters 的唤醒
+            let notified = queue.notify.notified();
+            {
+                let mut state = queue.state();
+                if !state.waiting.iter().any(|w| w == id) {
+                    state.waiting.push_back(id.to_string());
+                }
+                let position = state.waiting.iter().position(|w| w == id).unwrap_or(0);
+                if position == 0 && state.running < max_concurrent_tasks.max(1) {
+                    state.waiting.pop_front();
+                    state.running += 1;
+                    drop(state);
+                    // 队首离开后其余任务的位置都前移了
+                    queue.notify.notify_waiters();
+                    return Some(TaskSlot(Arc::clone(queue)));
+                }
+                if last_position != Some(position) {
+                    last_position = Some(position);
+                    on_position(position + 1);
+                }
+            }
+
+            tokio::select! {
+                _ = notified => {}
+                _ = control.cancelled() => {
+                    queue.leave(id);
+                    log::info!("任务 [{}] 在排队中被取消", id);
+                    return None;
+                }
+            }
         }
     }
 
     /// 添加任务
-    pub async fn add_task(&self, id: String, task: DownloadTask) -> anyhow::Result<()> {
+    ///
+    /// 查重、创建临时目录、登记任务在同一把锁内完成，
+    /// 避免同一 ID 的 start / delete / start 交错执行时，旧任务写入刚被删除的目录。
+    /// 返回值表示临时目录是否为本次新建。
+    pub async fn add_task(&self, id: String, task: DownloadTask) -> anyhow::Result<bool> {
         let mut tasks = self.tasks.lock().await;
-        
+
         // 检查是否已经存在同ID的任务
         if tasks.contains_key(&id) {
             log::warn!("任务 [{}] 已存在，拒绝重复添加。", id);
-            return Ok(());
+            return Err(anyhow::anyhow!("任务 [{}] 已存在", id));
         }
-        
+
+        // create_dir_all 对已存在的目录是幂等的，不会影响已下载的分片
+        let created = !tokio::fs::try_exists(&task.temp_dir).await.unwrap_or(false);
+        tokio::fs::create_dir_all(&task.temp_dir)
+            .await
+            .map_err(|e| anyhow::anyhow!("创建临时目录失败: {}", e))?;
+
         tasks.insert(id.clone(), task);
         log::info!("任务 [{}] 已添加", id);
-        
-        Ok(())
+
+        Ok(created)
     }
 
     /// 取消任务
-    /// 
-    /// 取消正在运行的下载任务，但保留临时目录以支持断点续传
-    pub async fn cancel_task(&self, id: &str) -> anyhow::Result<()>  {
+    ///
+    /// 取消正在运行的下载任务，但保留临时目录以支持断点续传。
+    /// 返回被取消任务的控制器，调用方可以用它等待任务真正停止。
+    pub async fn cancel_task(&self, id: &str) -> anyhow::Result<Option<Arc<DownloadControl>>> {
         let mut tasks = self.tasks.lock().await;
         if let Some(task) = tasks.remove(id) {
             // 设置取消标志
             task.cancel();
             log::info!("任务 [{}] 已取消", id);
+            Ok(Some(task.control))
         } else {
             log::warn!("任务 [{}] 不存在，无法取消", id);
+            Ok(None)
         }
-        Ok(())
     }
 
     /// 删除任务并清除临时目录
-    /// 
-    /// 删除任务和清理所有临时文件
-    pub async fn delete_task(&self, id: &str) -> anyhow::Result<()> {
+    ///
+    /// 如果任务正在运行，先设置取消标志再删除其临时目录；
+    /// 任务不在管理器中时，直接删除传入的 `temp_dir`。
+    /// 整个过程持有锁，不会与同 ID 的 `add_task` 交错。
+    pub async fn delete_task(&self, id: &str, temp_dir: &str) -> anyhow::Result<()> {
         let mut tasks = self.tasks.lock().await;
-        if let Some(task) = tasks.remove(id) {
-            // 删除临时目录
+        let temp_dir = match tasks.remove(id) {
+            Some(task) => {
+                task.cancel();
+                log::info!("任务 [{}] 已删除", id);
+                task.temp_dir
+            }
+            None => {
+                log::info!("任务 [{}] 不在管理器中，直接删除临时目录", id);
+                temp_dir.to_string()
+            }
+        };
+
+        if tokio::fs::try_exists(&temp_dir).await.unwrap_or(false) {
+            tokio::fs::remove_dir_all(&temp_dir).await?;
+            log::info!("任务 [{}] 临时下载目录: {} 已删除", id, temp_dir);
+        }
+        Ok(())
+    }
+
+    /// 取消并移除全部任务，删除它们的临时目录，返回被删除的任务 ID
+    ///
+    /// 单个临时目录删除失败不影响其余任务。
+    pub async fn delete_all_tasks(&self) -> Vec<String> {
+        let mut tasks = self.tasks.lock().await;
+        let mut ids = Vec::with_capacity(tasks.len());
+        for (id, task) in tasks.drain() {
+            task.cancel();
             if tokio::fs::try_exists(&task.temp_dir).await.unwrap_or(false) {
+                match tokio::fs::remove_dir_all(&task.temp_dir).await {
+                    Ok(()) => log::info!("任务 [{}] 临时下载目录: {} 已删除", id, task.temp_dir),
+                    Err(e) => log::warn!("任务 [{}] 删除临时目录失败: {}", id, e),
+                }
+            }
+            ids.push(id);
+        }
+        log::info!("已删除全部 {} 个任务", ids.len());
+        ids
+    }
+
+    /// 删除输出目录下不属于运行中任务的 `temp_*` 临时目录
+    ///
+    /// 按目录名中的任务 ID 判断任务是否运行中，`resumable` 中的任务尚未完成，目录同样保留。
+    /// 整个过程持有锁，不会与同 ID 的 `add_task` 交错。
+    pub async fn cleanup_temp_dirs(
+        &self,
+        output_dir: &str,
+        resumable: &HashSet<String>,
+    ) -> anyhow::Result<TempDirCleanup> {
+        let tasks = self.tasks.lock().await;
+        let mut cleanup = TempDirCleanup::default();
+        let mut entries = tokio::fs::read_dir(output_dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            let Some(id) = name.strip_prefix("temp_") else {
+                continue;
+            };
+            if !entry.file_type().await.map(|t| t.is_dir()).unwrap_or(false) {
+                continue;
+            }
+            if tasks.contains_key(id) {
+                cleanup.active.push(name);
+                continue;
+            }
+            if resumable.contains(id) {
+                cleanup.resumable.push(name);
+                continue;
+            }
+            match tokio::fs::remove_dir_all(entry.path()).await {
+                Ok(()) => cleanup.removed.push(name),
+                Err(e) => {
+                    log::warn!("删除临时目录 {} 失败: {}", entry.path().display(), e);
+                    cleanup.failed.push(name);
+                }
+            }
+        }
+        log::info!(
+            "清理临时目录 {}: 删除 {} 个，运行中保留 {} 个，未完成保留 {} 个，失败 {} 个",
+            output_dir,
+            cleanup.removed.len(),
+            cleanup.active.len(),
+            cleanup.resumable.len(),
+            cleanup.failed.len()
+        );
+        Ok(cleanup)
+    }
+
+    /// 任务结束时移除登记
+    ///
+    /// 仅当当前登记的仍是 `control` 所属的任务时才移除，
+    /// 防止旧任务收尾时误删同 ID 新任务的登记或临时目录。
+    pub async fn finish_task(
+        &self,
+        id: &str,
+        control: &Arc<DownloadControl>,
+        remove_temp_dir: bool,
+    ) -> anyhow::Result<()> {
+        let mut tasks = self.tasks.lock().await;
+        let is_current = tasks
+            .get(id)
+            .map(|t| Arc::ptr_eq(&t.control, control))
+            .unwrap_or(false);
+        if !is_current {
+            log::debug!("任务 [{}] 已被替换或移除，跳过收尾", id);
+            return Ok(());
+        }
+
+        if let Some(task) = tasks.remove(id) {
+            if remove_temp_dir && tokio::fs::try_exists(&task.temp_dir).await.unwrap_or(false) {
                 tokio::fs::remove_dir_all(&task.temp_dir).await?;
                 log::info!("任务 [{}] 临时下载目录: {} 已删除", id, task.temp_dir);
             }
-            log::info!("任务 [{}] 已删除", id);
-        } else {
-            log::warn!("任务 [{}] 不存在", id);
         }
         Ok(())
     }
@@ -107,8 +565,125 @@ impl DownloadManager {
         self.tasks.lock().await.contains_key(id)
     }
 
-    /// 获取任务的取消标志
-    pub async fn get_cancel_flag(&self, id: &str) -> Option<Arc<AtomicBool>> {
-        self.tasks.lock().await.get(id).map(|t| t.get_cancel_flag())
+    /// 所有运行中任务的状态快照
+    pub async fn list_tasks(&self) -> Vec<TaskStatus> {
+        let tasks = self.tasks.lock().await;
+        let mut statuses: Vec<TaskStatus> = tasks
+            .iter()
+            .map(|(id, task)| task.status(id, self.queue.position(id).is_some()))
+            .collect();
+        statuses.sort_by(|a, b| a.id.cmp(&b.id));
+        statuses
+    }
+
+    /// 运行中的任务数和所有任务按分片计算的总进度（0-100）
+    ///
+    /// 还没有任务解析完播放列表时总进度为 None。
+    pub async fn overall_progress(&self) -> (usize, Option<u32>) {
+        let tasks = self.tasks.lock().await;
+        let (mut completed, mut total) = (0, 0);
+        for metrics in tasks.values().filter_map(|t| t.metrics.get()) {
+            completed += metrics.completed_chunks.load(Ordering::Relaxed);
+            total += metrics.total_chunks;
+        }
+        let progress =
+            (total > 0).then(|| (completed as f64 / total as f64 * 100.0).floor() as u32);
+        (tasks.len(), progress)
+    }
+
+    /// 单个任务的状态快照，任务不在运行时返回 None
+    pub async fn task_status(&self, id: &str) -> Option<TaskStatus> {
+        let queued = self.queue.position(id).is_some();
+        let tasks = self.tasks.lock().await;
+        tasks.get(id).map(|t| t.status(id, queued))
+    }
+
+    /// 获取任务的控制器
+    pub async fn get_control(&self, id: &str) -> Option<Arc<DownloadControl>> {
+        self.tasks.lock().await.get(id).map(|t| t.get_control())
+    }
+
+    /// 暂停任务
+    pub async fn pause_task(&self, id: &str, mode: PauseMode) -> anyhow::Result<()> {
+        let tasks = self.tasks.lock().await;
+        match tasks.get(id) {
+            Some(task) => {
+                task.control.pause(mode);
+                log::info!("任务 [{}] 已暂停 ({:?})", id, mode);
+                Ok(())
+            }
+            None => Err(anyhow::anyhow!("任务 [{}] 不存在，无法暂停", id)),
+        }
+    }
+
+    /// 暂停全部任务，退出应用前调用
+    ///
+    /// 返回各任务的下载统计槽位，调用方据此等待正在下载的分片结束。
+    pub async fn pause_all(&self) -> Vec<MetricsSlot> {
+        let tasks = self.tasks.lock().await;
+        tasks
+            .iter()
+            .map(|(id, task)| {
+                task.control.pause(PauseMode::Soft);
+                log::info!("任务 [{}] 因退出应用而暂停", id);
+                task.metrics_slot()
+            })
+            .collect()
+    }
+
+    /// 恢复任务
+    pub async fn resume_task(&self, id: &str) -> anyhow::Result<()> {
+        let tasks = self.tasks.lock().await;
+        match tasks.get(id) {
+            Some(task) => {
+                task.control.resume();
+                log::info!("任务 [{}] 已恢复", id);
+                Ok(())
+            }
+            None => Err(anyhow::anyhow!("任务 [{}] 不存在，无法恢复", id)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 同一 ID 快速地开始、删除、再开始：旧任务收尾时不能删掉新任务的登记和临时目录
+    #[tokio::test]
+    async fn start_delete_start_keeps_new_task() {
+        let dir = std::env::temp_dir().join(format!("m3u8_manager_test_{}", std::process::id()));
+        let temp_dir = dir.to_string_lossy().into_owned();
+        let manager = DownloadManager::new();
+        let id = "task".to_string();
+
+        let first = DownloadTask::new("video".into(), temp_dir.clone());
+        let first_control = first.get_control();
+        assert!(manager.add_task(id.clone(), first).await.unwrap());
+        let duplicate = DownloadTask::new("video".into(), temp_dir.clone());
+        assert!(manager.add_task(id.clone(), duplicate).await.is_err());
+        tokio::fs::write(dir.join("part_0.ts"), b"old")
+            .await
+            .unwrap();
+
+        manager.delete_task(&id, &temp_dir).await.unwrap();
+        assert!(first_control.is_cancelled());
+        assert!(!dir.exists());
+
+        let second = DownloadTask::new("video".into(), temp_dir.clone());
+        let second_control = second.get_control();
+        assert!(manager.add_task(id.clone(), second).await.unwrap());
+        // 被删除的旧任务这时才收尾
+        manager
+            .finish_task(&id, &first_control, true)
+            .await
+            .unwrap();
+        let dir_exists = dir.exists();
+        let registered = manager.get_control(&id).await;
+        tokio::fs::remove_dir_all(&dir).await.ok();
+
+        assert!(dir_exists);
+        assert!(registered.is_some_and(|c| Arc::ptr_eq(&c, &second_control)));
+        assert!(!second_control.is_cancelled());
     }
-}
\ No newline at end of file
+}
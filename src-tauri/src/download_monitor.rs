@@ -1,17 +1,103 @@
 //! 下载监控模块
-//! 负责实时计算下载速度、检查任务状态（取消）
+//! 负责实时计算下载速度、检查任务状态（取消、暂停）
 //! 并通过 Tauri 事件（`download_progress`）向前端报告状态。
 
+use crate::disk_space::format_bytes;
+use crate::download_manager::DownloadControl;
+use crate::task_events::{
+    emit_task_event, emit_task_event_with, legacy_events_enabled, TaskPhase, TaskUpdate,
+};
+use crate::tray;
+use serde::Serialize;
 use serde_json::json;
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use std::sync::{
-    atomic::{AtomicBool, AtomicUsize, Ordering},
+    atomic::{AtomicUsize, Ordering},
     Arc,
 };
 use std::time::{Duration, Instant};
-use tauri::{AppHandle, Emitter};
+use tauri::AppHandle;
+use tauri_plugin_store::StoreExt;
 use tokio::sync::Mutex;
 
+/// 默认进度事件间隔（毫秒）
+const DEFAULT_PROGRESS_INTERVAL_MS: u64 = 1000;
+/// 进度事件间隔允许的范围（毫秒）
+const MIN_PROGRESS_INTERVAL_MS: u64 = 200;
+const MAX_PROGRESS_INTERVAL_MS: u64 = 5000;
+/// 速度统计窗口的最小长度，避免事件间隔很短时速度抖动
+const MIN_SPEED_WINDOW: Duration = Duration::from_secs(1);
+/// 剩余时间指数移动平均的权重，越小越平滑
+const ETA_SMOOTHING: f64 = 0.2;
+
+/// 将字节/秒转换为带单位的速度
+fn speed_with_unit(bytes_per_sec: f64) -> (f64, &'static str) {
+    let speed_kb = bytes_per_sec / 1024.0;
+    if speed_kb >= 1024.0 {
+        (speed_kb / 1024.0, "MB/s")
+    } else {
+        (speed_kb, "KB/s")
+    }
+}
+
+/// 从 settings.dat 读取 `progressIntervalMs`，并限制在合理范围内
+fn read_progress_interval(app_handle: &AppHandle) -> Duration {
+    let ms = app_handle
+        .store("settings.dat")
+        .ok()
+        .and_then(|s| s.get("progressIntervalMs"))
+        .and_then(|v| v.as_u64().or_else(|| v.as_str().and_then(|s| s.parse().ok())))
+        .unwrap_or(DEFAULT_PROGRESS_INTERVAL_MS);
+    Duration::from_millis(ms.clamp(MIN_PROGRESS_INTERVAL_MS, MAX_PROGRESS_INTERVAL_MS))
+}
+
+/// 分片大小分布统计（字节）
+#[derive(Debug, Clone, Serialize)]
+pub struct SegmentSizeStats {
+    pub count: usize,
+    pub min: usize,
+    pub max: usize,
+    pub avg: usize,
+    pub median: usize,
+}
+
+/// 正在进行的分片请求计数守卫，drop 时减一（包括任务被取消、future 被丢弃的情况）
+pub struct InFlightGuard(Arc<AtomicUsize>);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// 正在下载的分片的进度：分片下标 -> (已接收字节, 预期总字节)
+type SegmentProgressMap = Arc<std::sync::Mutex<HashMap<usize, (u64, Option<u64>)>>>;
+
+/// 单个分片的下载进度，离开作用域时自动移除
+pub struct SegmentProgressGuard {
+    progress: SegmentProgressMap,
+    index: usize,
+}
+
+impl SegmentProgressGuard {
+    /// 更新已接收的字节数，`expected` 为 None 表示分块传输、总大小未知
+    pub fn update(&self, received: u64, expected: Option<u64>) {
+        self.progress
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(self.index, (received, expected));
+    }
+}
+
+impl Drop for SegmentProgressGuard {
+    fn drop(&mut self) {
+        self.progress
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.index);
+    }
+}
+
 /// 下载指标跟踪结构体（增强版）
 /// 负责存储下载过程中的所有实时数据。
 #[derive(Clone)]
@@ -20,7 +106,21 @@ pub struct DownloadMetrics {
     pub total_bytes: Arc<AtomicUsize>,
     pub downloaded_bytes: Arc<AtomicUsize>,
     pub completed_chunks: Arc<AtomicUsize>,
+    pub skipped_chunks: Arc<AtomicUsize>, // 内容为空或无效而被跳过的分片数
+    pub in_flight: Arc<AtomicUsize>,      // 正在发起请求的分片数（不含等待信号量的）
+    // 按 Content-Length 抽样估算的总字节数，0 表示未知
+    pub estimated_total_bytes: Arc<AtomicUsize>,
     speed_samples: Arc<Mutex<VecDeque<(Instant, usize)>>>, // 原始采样数据 (Instant, bytes)
+    // 每个已完成分片的大小，仅在分片写入时记录一次，用于生成大小分布统计
+    segment_sizes: Arc<std::sync::Mutex<Vec<usize>>>,
+    // 平滑后的剩余时间（秒）
+    eta_ema: Arc<std::sync::Mutex<Option<f64>>>,
+    // 容忍缺失分片时，最终缺失的分片（下标, 本地路径）
+    missing: Arc<std::sync::Mutex<Vec<(usize, String)>>>,
+    // 跳过无效分片时，内容为空或无效的分片（下标, 本地路径）
+    skipped: Arc<std::sync::Mutex<Vec<(usize, String)>>>,
+    // 正在下载的分片的进度，用于在大分片下载期间平滑进度
+    segment_progress: SegmentProgressMap,
 }
 
 impl DownloadMetrics {
@@ -30,15 +130,153 @@ impl DownloadMetrics {
             total_bytes: Arc::new(AtomicUsize::new(0)),
             downloaded_bytes: Arc::new(AtomicUsize::new(0)),
             completed_chunks: Arc::new(AtomicUsize::new(0)),
+            skipped_chunks: Arc::new(AtomicUsize::new(0)),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            estimated_total_bytes: Arc::new(AtomicUsize::new(0)),
             speed_samples: Arc::new(Mutex::new(VecDeque::with_capacity(10))),
+            segment_sizes: Arc::new(std::sync::Mutex::new(Vec::with_capacity(total_chunks))),
+            eta_ema: Arc::new(std::sync::Mutex::new(None)),
+            missing: Arc::new(std::sync::Mutex::new(Vec::new())),
+            skipped: Arc::new(std::sync::Mutex::new(Vec::new())),
+            segment_progress: SegmentProgressMap::default(),
         }
     }
 
+    /// 标记一个分片请求开始，返回的守卫在离开作用域时自动减计数
+    pub fn start_in_flight(&self) -> InFlightGuard {
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        InFlightGuard(Arc::clone(&self.in_flight))
+    }
+
+    /// 开始跟踪一个分片的下载进度，返回的守卫在离开作用域时自动移除
+    pub fn track_segment(&self, index: usize) -> SegmentProgressGuard {
+        SegmentProgressGuard {
+            progress: Arc::clone(&self.segment_progress),
+            index,
+        }
+    }
+
+    /// 正在下载的分片已接收的字节数之和
+    pub fn in_progress_bytes(&self) -> u64 {
+        self.segment_progress
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .values()
+            .map(|(received, _)| received)
+            .sum()
+    }
+
+    /// 正在下载的分片按已接收比例折算的分片数，总大小未知的分片不计入
+    fn in_progress_chunks(&self) -> f64 {
+        self.segment_progress
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .values()
+            .filter_map(|&(received, expected)| {
+                let expected = expected.filter(|&e| e > 0)?;
+                Some((received as f64 / expected as f64).min(1.0))
+            })
+            .sum()
+    }
+
+    /// 记录一个最终缺失的分片
+    pub fn record_missing_segment(&self, index: usize, path: &str) {
+        self.missing
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push((index, path.to_string()));
+    }
+
+    /// 按下标排序的缺失分片列表
+    pub fn missing_segments(&self) -> Vec<(usize, String)> {
+        let mut missing = self
+            .missing
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        missing.sort_unstable();
+        missing
+    }
+
+    /// 记录一个内容为空或无效而被跳过的分片
+    pub fn record_skipped_segment(&self, index: usize, path: &str) {
+        self.skipped
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push((index, path.to_string()));
+    }
+
+    /// 按下标排序的无效分片列表
+    pub fn skipped_segments(&self) -> Vec<(usize, String)> {
+        let mut skipped = self
+            .skipped
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        skipped.sort_unstable();
+        skipped
+    }
+
+    fn missing_count(&self) -> usize {
+        self.missing.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    fn skipped_count(&self) -> usize {
+        self.skipped.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    /// 所有分片是否都已结束（缺失分片和跳过的无效分片不会再下载，计入已结束）
+    pub fn is_downloaded(&self) -> bool {
+        let finished = self.completed_chunks.load(Ordering::Relaxed)
+            + self.missing_count()
+            + self.skipped_count();
+        self.total_chunks > 0 && finished == self.total_chunks
+    }
+
+    /// 记录一个已完成分片的大小
+    pub fn record_segment_size(&self, size: usize) {
+        self.segment_sizes
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(size);
+    }
+
+    /// 计算分片大小分布（最小/最大/平均/中位数），没有记录时返回 None
+    pub fn segment_size_stats(&self) -> Option<SegmentSizeStats> {
+        let mut sizes = self
+            .segment_sizes
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        if sizes.is_empty() {
+            return None;
+        }
+        sizes.sort_unstable();
+        let count = sizes.len();
+        let median = if count % 2 == 0 {
+            (sizes[count / 2 - 1] + sizes[count / 2]) / 2
+        } else {
+            sizes[count / 2]
+        };
+        Some(SegmentSizeStats {
+            count,
+            min: sizes[0],
+            max: sizes[count - 1],
+            avg: sizes.iter().sum::<usize>() / count,
+            median,
+        })
+    }
+
     /// 累加预估的总字节数
     pub fn update_total_bytes(&self, size: usize) {
         self.total_bytes.fetch_add(size, Ordering::Relaxed);
     }
 
+    /// 设置按抽样估算的总字节数
+    pub fn set_estimated_total_bytes(&self, estimate: usize) {
+        self.estimated_total_bytes.store(estimate, Ordering::Relaxed);
+    }
+
     /// 记录已下载的数据块，用于计算速度。
     pub async fn record_chunk(&self, size: usize) {
         let now = Instant::now();
@@ -52,31 +290,23 @@ impl DownloadMetrics {
         self.downloaded_bytes.fetch_add(size, Ordering::Relaxed);
     }
 
-    /// 获取窗口平均速度（如过去1秒）
-    async fn get_windowed_speed(&self) -> (f64, &'static str) {
+    /// 获取窗口内的平均速度（字节/秒）
+    async fn get_windowed_bytes_per_sec(&self, window: Duration) -> f64 {
         let now = Instant::now();
         let samples = self.speed_samples.lock().await;
-        // 只考虑过去 1 秒的采样
-        let cutoff = now - Duration::from_secs(1);
+        // 只考虑窗口内的采样
+        let cutoff = now - window;
         let relevant: Vec<_> = samples.iter().filter(|(t, _)| *t >= cutoff).collect();
         if relevant.is_empty() {
-            return (0.0, "KB/s");
+            return 0.0;
         }
         let total_bytes: usize = relevant.iter().map(|&(_, size)| size).sum();
         let duration = now.duration_since(cutoff).as_secs_f64().max(0.5); // 避免除零
-        let bytes_per_second = total_bytes as f64 / duration;
-        let speed_kb = bytes_per_second / 1024.0;
-
-        // 速度单位转换
-        if speed_kb >= 1024.0 {
-            (speed_kb / 1024.0, "MB/s")
-        } else {
-            (speed_kb, "KB/s")
-        }
+        total_bytes as f64 / duration
     }
 
     /// 获取进度百分比
-    async fn get_progress(&self) -> f64 {
+    pub fn get_progress(&self) -> f64 {
         if self.total_chunks == 0 {
             0.0
         } else {
@@ -88,80 +318,400 @@ impl DownloadMetrics {
             (chunks / self.total_chunks as f64 * 100.0).clamp(0.0, 100.0)
         }
     }
+
+    /// 计入正在下载分片部分进度的进度百分比
+    ///
+    /// 分片很大时，单个分片下载期间进度条也能持续前进；
+    /// 下载结束前不会显示 100%。
+    pub fn get_smooth_progress(&self) -> f64 {
+        if self.total_chunks == 0 {
+            return 0.0;
+        }
+        let chunks = self.completed_chunks.load(Ordering::Relaxed);
+        if chunks >= self.total_chunks {
+            return 100.0;
+        }
+        let blended = chunks as f64 + self.in_progress_chunks();
+        (blended / self.total_chunks as f64 * 100.0).clamp(0.0, 99.0)
+    }
+
+    /// 估算剩余字节数
+    ///
+    /// 已估算总大小时直接相减；否则按已完成分片的平均大小乘以剩余分片数。
+    fn remaining_bytes(&self) -> Option<f64> {
+        let downloaded = self.downloaded_bytes.load(Ordering::Relaxed) as f64;
+        let estimated = self.estimated_total_bytes.load(Ordering::Relaxed);
+        if estimated > 0 {
+            return Some((estimated as f64 - downloaded).max(0.0));
+        }
+        let completed = self.completed_chunks.load(Ordering::Relaxed);
+        if completed == 0 {
+            return None;
+        }
+        let remaining_chunks = self.total_chunks.saturating_sub(completed) as f64;
+        Some(downloaded / completed as f64 * remaining_chunks)
+    }
+
+    /// 计算平滑后的剩余时间（秒）
+    ///
+    /// 对每次的估算值做指数移动平均，避免随瞬时速度大幅跳动；
+    /// 速度为 0 或无法估算剩余大小时返回 None。
+    fn get_eta(&self, bytes_per_sec: f64) -> Option<u64> {
+        let mut ema = self.eta_ema.lock().unwrap_or_else(|e| e.into_inner());
+        let remaining = match self.remaining_bytes() {
+            Some(remaining) if bytes_per_sec > 0.0 => remaining,
+            _ => return None,
+        };
+        let eta = remaining / bytes_per_sec;
+        let smoothed = match *ema {
+            Some(previous) => previous + ETA_SMOOTHING * (eta - previous),
+            None => eta,
+        };
+        *ema = Some(smoothed);
+        Some(smoothed.round() as u64)
+    }
+
+    /// 按字节计算的进度百分比
+    ///
+    /// 未能估算总大小（服务器不返回 Content-Length）时返回 None，由前端继续使用按分片计算的进度。
+    fn get_byte_progress(&self) -> Option<f64> {
+        let estimated = self.estimated_total_bytes.load(Ordering::Relaxed);
+        if estimated == 0 {
+            return None;
+        }
+        let downloaded = self.downloaded_bytes.load(Ordering::Relaxed) as f64;
+        // 估算值可能偏小，下载未结束前不显示 100%
+        Some((downloaded / estimated as f64 * 100.0).clamp(0.0, 99.0))
+    }
+}
+
+/// 发送一次准备阶段事件 `download_preparing`
+///
+/// `counts` 为该步骤的计数字段，同时写入事件内容和任务状态的 details。
+fn emit_preparing(
+    app_handle: &AppHandle,
+    legacy_events: bool,
+    id: &str,
+    step: &str,
+    message: String,
+    counts: serde_json::Map<String, serde_json::Value>,
+) {
+    let mut details = counts.clone();
+    details.insert("step".into(), json!(step));
+    let mut payload = counts;
+    payload.insert("id".into(), json!(id));
+    payload.insert("phase".into(), json!(step));
+    payload.insert("message".into(), json!(message));
+
+    let mut update = TaskUpdate::new(id, TaskPhase::Preparing, 2, message);
+    update.details = Some(details.into());
+    emit_task_event_with(
+        app_handle,
+        legacy_events,
+        "download_preparing",
+        payload.into(),
+        update,
+    )
+    .ok();
+}
+
+/// 通知前端开始解析播放列表
+///
+/// 监控任务在解析完成后才启动，分片和密钥很多时解析可能持续数秒，
+/// 先发送一次 `download_preparing` 事件，界面显示“解析中”。
+pub fn emit_parsing(id: &str, app_handle: &AppHandle) {
+    emit_preparing(
+        app_handle,
+        legacy_events_enabled(app_handle),
+        id,
+        "parsing",
+        "解析中".to_string(),
+        serde_json::Map::new(),
+    );
+}
+
+/// 密钥数量超过该值时才上报获取进度
+const KEY_PROGRESS_MIN_KEYS: usize = 5;
+/// 分片数量超过该值时才上报断点续传扫描进度
+const RESUME_SCAN_PROGRESS_MIN_SEGMENTS: usize = 500;
+/// 准备阶段进度事件的最小间隔
+const PREPARING_PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+/// 需要上报进度的准备步骤
+#[derive(Debug, Clone, Copy)]
+pub enum PreparingStep {
+    /// 获取密钥，上报 `keys_fetched / total_keys`
+    Keys,
+    /// 断点续传时检查已下载的分片，上报 `checked / total`
+    ResumeScan,
+}
+
+impl PreparingStep {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Keys => "keys",
+            Self::ResumeScan => "resume_scan",
+        }
+    }
+
+    /// 总数超过该值时才上报进度，数量少时很快就能完成
+    fn min_total(self) -> usize {
+        match self {
+            Self::Keys => KEY_PROGRESS_MIN_KEYS,
+            Self::ResumeScan => RESUME_SCAN_PROGRESS_MIN_SEGMENTS,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Keys => "获取密钥",
+            Self::ResumeScan => "检查已下载分片",
+        }
+    }
+
+    /// 事件中（已完成数，总数）的字段名
+    fn count_fields(self) -> (&'static str, &'static str) {
+        match self {
+            Self::Keys => ("keys_fetched", "total_keys"),
+            Self::ResumeScan => ("checked", "total"),
+        }
+    }
+}
+
+/// 准备阶段（监控任务启动前）的进度
+///
+/// 通过 `download_preparing` 事件上报 `done / total`，避免界面看起来卡住；
+/// 总数较少时不发送，并按时间节流，最后一项完成时总会发送。
+pub struct PreparingProgress {
+    id: String,
+    app_handle: AppHandle,
+    legacy_events: bool,
+    step: PreparingStep,
+    total: usize,
+    done: usize,
+    last_emit_time: Option<Instant>,
+}
+
+impl PreparingProgress {
+    pub fn new(id: &str, app_handle: &AppHandle, step: PreparingStep, total: usize) -> Self {
+        Self {
+            id: id.to_string(),
+            app_handle: app_handle.clone(),
+            legacy_events: legacy_events_enabled(app_handle),
+            step,
+            total,
+            done: 0,
+            last_emit_time: None,
+        }
+    }
+
+    /// 记录完成一项
+    pub fn advance(&mut self) {
+        self.done += 1;
+        if self.total <= self.step.min_total() {
+            return;
+        }
+
+        let now = Instant::now();
+        let finished = self.done >= self.total;
+        let due = self
+            .last_emit_time
+            .map(|t| now.duration_since(t) >= PREPARING_PROGRESS_INTERVAL)
+            .unwrap_or(true);
+        if !(due || finished) {
+            return;
+        }
+
+        let (done_field, total_field) = self.step.count_fields();
+        let mut counts = serde_json::Map::new();
+        counts.insert(done_field.into(), json!(self.done));
+        counts.insert(total_field.into(), json!(self.total));
+        emit_preparing(
+            &self.app_handle,
+            self.legacy_events,
+            &self.id,
+            self.step.name(),
+            format!("{} {}/{}", self.step.label(), self.done, self.total),
+            counts,
+        );
+        self.last_emit_time = Some(now);
+    }
+}
+
+/// 上报容忍模式下最终缺失的分片
+///
+/// 通过 `segments_missing` 事件发送缺失分片的下标，便于用户决定是否重新下载。
+pub fn emit_missing_segments(app_handle: &AppHandle, id: &str, missing: &[(usize, String)]) {
+    let indices: Vec<usize> = missing.iter().map(|(index, _)| *index).collect();
+    let message = format!("{} 个分片缺失，已跳过", indices.len());
+    let mut update = TaskUpdate::new(id, TaskPhase::Downloaded, 3, message.clone());
+    update.details = Some(json!({ "missing_segments": indices }));
+    emit_task_event(
+        app_handle,
+        "segments_missing",
+        json!({
+            "id": id,
+            "missing_segments": indices,
+            "message": message,
+        }),
+        update,
+    )
+    .ok();
+}
+
+/// 上报内容为空或无效而被跳过的分片
+///
+/// 通过 `segments_skipped` 事件发送这些分片的下标，合并结果在对应位置会有空缺。
+pub fn emit_skipped_segments(app_handle: &AppHandle, id: &str, skipped: &[(usize, String)]) {
+    let indices: Vec<usize> = skipped.iter().map(|(index, _)| *index).collect();
+    let message = format!("{} 个分片内容为空或无效，已跳过", indices.len());
+    let mut update = TaskUpdate::new(id, TaskPhase::Downloaded, 3, message.clone());
+    update.details = Some(json!({ "skipped_segments": indices }));
+    emit_task_event(
+        app_handle,
+        "segments_skipped",
+        json!({
+            "id": id,
+            "skipped_segments": indices,
+            "message": message,
+        }),
+        update,
+    )
+    .ok();
 }
 
 /// 运行下载监控任务
 /// 这是一个独立的 Tokio 任务，持续监听下载指标并向前端发送事件。
 pub async fn run_monitor_task(
     id: String,
-    cancelled: Arc<AtomicBool>,
+    control: Arc<DownloadControl>,
     metrics: Arc<DownloadMetrics>,
     app_handle: AppHandle,
 ) -> tokio::task::JoinHandle<()> {
+    // 事件发送间隔可配置；速度窗口不短于 1 秒，且覆盖整个发送间隔
+    let emit_interval = read_progress_interval(&app_handle);
+    let speed_window = emit_interval.max(MIN_SPEED_WINDOW);
+    // 设置在任务开始时读取一次，发送进度时不再访问设置存储
+    let legacy_events = legacy_events_enabled(&app_handle);
+
     tokio::spawn(async move {
         // 创建定时器
         // 将检查周期缩短到 100ms，确保取消指令响应迅速
         let mut interval = tokio::time::interval(Duration::from_millis(100));
 
         // 记录上一次发送事件的时间
-        // 初始化为 1 秒前，确保任务启动时能立即发送第一次状态
-        let mut last_emit_time = Instant::now() - Duration::from_secs(1);
+        // 初始化为一个发送间隔之前，确保任务启动时能立即发送第一次状态
+        let mut last_emit_time = Instant::now() - emit_interval;
         let mut last_data: Option<serde_json::Value> = None;
         loop {
             // 定时器触发
             interval.tick().await;
 
             // --- 获取并构建状态数据 ---
-            let is_cancelled = cancelled.load(Ordering::Relaxed);
+            let is_cancelled = control.is_cancelled();
+            let is_paused = control.is_paused();
             let chunks_completed = metrics.completed_chunks.load(Ordering::Relaxed);
             let chunks_total = metrics.total_chunks;
-            let is_downloaded = chunks_total > 0 && chunks_completed == chunks_total;
+            let is_downloaded = metrics.is_downloaded();
             let final_state = is_cancelled || is_downloaded;
 
             let now = Instant::now();
-            let time_since_last_emit = now.duration_since(last_emit_time).as_millis();
+            let time_since_last_emit = now.duration_since(last_emit_time);
 
             // 只有满足以下任一条件时才发送事件：
-            // 1. 距离上次发送已超过 1000ms (限制更新频率)
+            // 1. 距离上次发送已超过设定的间隔 (限制更新频率)
             // 2. 任务已完成或被取消 (必须立即反馈给 UI)
-            if time_since_last_emit >= 1000 || final_state {
-                let progress = metrics.get_progress().await;
+            if time_since_last_emit >= emit_interval || final_state {
+                let progress = metrics.get_smooth_progress();
 
-                let (speed_val, speed_unit) = if final_state {
-                    (0.0, "KB/s") // 结束状态速度归零
+                let bytes_per_sec = if final_state || is_paused {
+                    0.0 // 结束或暂停状态速度归零
                 } else {
-                    metrics.get_windowed_speed().await
+                    metrics.get_windowed_bytes_per_sec(speed_window).await
                 };
+                let (speed_val, speed_unit) = speed_with_unit(bytes_per_sec);
+                let eta_secs = metrics.get_eta(bytes_per_sec);
 
                 // 构建状态元数据
                 let status_info = match (is_cancelled, is_downloaded) {
-                    (true, _) => (0, "已取消"),       // cancelled
-                    (false, false) => (2, "下载中"),  // downloading
-                    (false, true) => (3, "下载完成"), // downloaded
+                    (true, _) => (0, "已取消"),                    // cancelled
+                    (false, false) if is_paused => (6, "已暂停"), // paused
+                    (false, false) => (2, "下载中"),               // downloading
+                    (false, true) => (3, "下载完成"),              // downloaded
+                };
+                let phase = match status_info.0 {
+                    0 => TaskPhase::Cancelled,
+                    2 => TaskPhase::Downloading,
+                    6 => TaskPhase::Paused,
+                    _ => TaskPhase::Downloaded,
+                };
+                let progress = progress.floor() as u32;
+                let speed = format!("{:.2} {}", speed_val, speed_unit);
+
+                let byte_progress = if is_downloaded {
+                    metrics.get_byte_progress().map(|_| 100)
+                } else {
+                    metrics.get_byte_progress().map(|p| p.floor() as u32)
+                };
+                let estimated_total_bytes = metrics.estimated_total_bytes.load(Ordering::Relaxed);
+                let downloaded_bytes = metrics.downloaded_bytes.load(Ordering::Relaxed);
+                let total_bytes = metrics.total_bytes.load(Ordering::Relaxed);
+                // 已知预估总大小时优先显示预估值，否则显示已完成分片的大小之和
+                let total_human = format_bytes(if estimated_total_bytes > 0 {
+                    estimated_total_bytes as u64
+                } else {
+                    total_bytes as u64
+                });
+
+                // 分片大小分布只在下载完成时计算一次
+                let segment_sizes = if is_downloaded && !is_cancelled {
+                    metrics.segment_size_stats()
+                } else {
+                    None
                 };
 
                 // 生成当前事件数据
                 let current_data = json!({
                     "id": id,
-                    "progress": progress.floor() as u32,
-                    "speed": format!("{:.2} {}", speed_val, speed_unit),
+                    "progress": progress,
+                    "speed": speed,
                     "status": status_info.0,
                     "message": status_info.1,
                     "isMerged": false,
+                    "eta_secs": eta_secs,
                     "details": {
                         "chunks": chunks_completed,
                         "total_chunks": chunks_total,
-                        "downloaded": metrics.downloaded_bytes.load(Ordering::Relaxed),
-                        "total_bytes": metrics.total_bytes.load(Ordering::Relaxed),
+                        "downloaded": downloaded_bytes,
+                        "total_bytes": total_bytes,
+                        "downloaded_human": format_bytes(downloaded_bytes as u64),
+                        "total_human": total_human,
+                        "estimated_total_bytes": (estimated_total_bytes > 0)
+                            .then_some(estimated_total_bytes),
+                        "byte_progress": byte_progress,
+                        "segment_sizes": segment_sizes,
+                        "in_flight": metrics.in_flight.load(Ordering::Relaxed),
+                        "in_progress_bytes": metrics.in_progress_bytes(),
                     }
                 });
 
                 // 发送事件 (去重检查)
                 if last_data.as_ref() != Some(&current_data) {
-                    app_handle
-                        .emit("download_progress", current_data.clone())
-                        .ok();
+                    let mut update = TaskUpdate::new(&id, phase, status_info.0, status_info.1);
+                    update.progress = Some(progress);
+                    update.speed = Some(speed);
+                    update.eta = eta_secs;
+                    update.details = Some(current_data["details"].clone());
+                    emit_task_event_with(
+                        &app_handle,
+                        legacy_events,
+                        "download_progress",
+                        current_data.clone(),
+                        update,
+                    )
+                    .ok();
                     last_data = Some(current_data);
+                    tray::refresh_tooltip_throttled(&app_handle).await;
                 }
 
                 last_emit_time = now;
@@ -1,6 +1,10 @@
 use crate::commands::{
-    cancel_download, check_update, delete_download, delete_file, get_cpu_info, save_settings,
-    save_store_file, start_download,
+    batch_download, cancel_download, check_update, cleanup_temp_dirs, delete_all_tasks,
+    delete_download, delete_file, export_logs, get_cpu_info, get_data_usage,
+    get_log_dir_path_string, get_task_status, list_tasks, list_tracks, merge_existing,
+    open_log_dir, open_output_dir, pause_download, probe_m3u8, reset_data_usage, restart_download,
+    resume_download, resume_incomplete_tasks, reveal_file, save_settings, save_store_file,
+    self_test, set_log_level, start_download, verify_download,
 };
 use crate::download_manager::DownloadManager;
 use tauri::{
@@ -10,12 +14,26 @@ use tauri::{
 };
 use tauri_plugin_store::StoreExt;
 
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub mod bench;
 pub mod commands;
+mod data_usage;
+mod diagnostics;
+mod disk_space;
 mod download;
 mod download_manager;
 mod download_monitor;
 mod logger;
+mod m3u8;
 mod merge;
+mod notification;
+mod rate_limit;
+mod sample_aes;
+mod shutdown;
+mod task_events;
+mod task_registry;
+mod tray;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -44,6 +62,7 @@ pub fn run() {
             }
         }))
         .plugin(tauri_plugin_http::init())
+        .plugin(tauri_plugin_notification::init())
         .plugin(tauri_plugin_dialog::init())
         .manage(DownloadManager::new()) // 注册下载全局状态管理
         .plugin(tauri_plugin_fs::init())
@@ -88,8 +107,9 @@ pub fn run() {
                             }
                         } else {
                             println!("用户请求退出，正在关闭程序...");
-                            log::info!("Exit the application...");
-                            app_handle.exit(0);
+                            // 收尾完成后再退出，窗口先保留
+                            _api.prevent_close();
+                            shutdown::exit_gracefully(app_handle);
                         }
                     }
                 });
@@ -99,13 +119,35 @@ pub fn run() {
         })
         .invoke_handler(tauri::generate_handler![
             start_download,
+            batch_download,
             cancel_download,
+            pause_download,
+            resume_download,
             delete_download,
+            delete_all_tasks,
+            cleanup_temp_dirs,
             get_cpu_info,
             delete_file,
             save_settings,
             check_update,
             save_store_file,
+            set_log_level,
+            open_log_dir,
+            open_output_dir,
+            reveal_file,
+            get_log_dir_path_string,
+            export_logs,
+            self_test,
+            get_data_usage,
+            reset_data_usage,
+            verify_download,
+            merge_existing,
+            restart_download,
+            list_tracks,
+            probe_m3u8,
+            resume_incomplete_tasks,
+            list_tasks,
+            get_task_status,
         ])
         .build(tauri::generate_context!())
         .expect("error while running tauri application")
@@ -127,8 +169,13 @@ pub fn run() {
                         // 阻止进程死亡，托盘存活！
                         api.prevent_exit();
                         println!("Wayland/Linux 退出拦截：已阻止进程退出，保持托盘后台运行");
+                        return;
                     }
-                    // 如果 should_minimize 为 false，这里什么都不做，程序将顺理成章地真正退出。
+                }
+                // 其余退出请求（包括系统注销、Cmd+Q）先暂停任务并保存登记，收尾完成后再真正退出
+                if !shutdown::ready_to_exit() {
+                    api.prevent_exit();
+                    shutdown::exit_gracefully(app_handle);
                 }
             }
         });
@@ -202,8 +249,8 @@ pub fn get_or_create_main_window(app: &AppHandle) -> Option<tauri::WebviewWindow
                         }
                     } else {
                         println!("用户请求退出，正在关闭程序...");
-                        log::info!("Exit the application...");
-                        app_handle.exit(0);
+                        _api.prevent_close();
+                        shutdown::exit_gracefully(app_handle);
                     }
                 }
             });
@@ -238,10 +285,10 @@ fn enable_tray(app: &mut tauri::App) -> tauri::Result<()> {
         .item(&quit_item)
         .build()?;
 
-    let _tray = TrayIconBuilder::with_id("tray")
+    let _tray = TrayIconBuilder::with_id(tray::TRAY_ID)
         .show_menu_on_left_click(false)
         .icon(app.default_window_icon().unwrap().clone())
-        .tooltip("m3u8视频下载器")
+        .tooltip(tray::DEFAULT_TOOLTIP)
         .menu(&menu)
         .on_tray_icon_event(|tray, event| match event {
             // 左键单击托盘图标
@@ -278,8 +325,7 @@ fn enable_tray(app: &mut tauri::App) -> tauri::Result<()> {
                 }
             }
             "quit" => {
-                log::info!("Exit the application...");
-                app.exit(0);
+                shutdown::exit_gracefully(app);
             }
             _ => {
                 println!("menu item {:?} not handled", event.id);
@@ -0,0 +1,62 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// 压缩包中系统信息文件的名称
+const SYSTEM_INFO_FILE: &str = "system_info.txt";
+
+/// 把日志目录中的全部文件（含滚动和已压缩的日志）与系统信息打包为 zip，写入 `dest`
+///
+/// 只打包日志目录第一层的文件；已压缩的 `.gz` 日志直接存储，不再重复压缩。
+/// 写入失败时删除不完整的压缩包。返回打包的日志文件数。
+pub fn export_logs(log_dir: &Path, dest: &Path, system_info: &str) -> io::Result<usize> {
+    let result = write_log_archive(log_dir, dest, system_info);
+    if result.is_err() {
+        fs::remove_file(dest).ok();
+    }
+    result
+}
+
+fn write_log_archive(log_dir: &Path, dest: &Path, system_info: &str) -> io::Result<usize> {
+    let mut zip = ZipWriter::new(fs::File::create(dest)?);
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+
+    zip.start_file(SYSTEM_INFO_FILE, deflated)?;
+    zip.write_all(system_info.as_bytes())?;
+
+    let mut paths: Vec<_> = fs::read_dir(log_dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_file())
+        .collect();
+    paths.sort();
+
+    let mut count = 0;
+    for path in paths {
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        // 日志仍在写入，读到多少打包多少；单个文件读取失败不影响其余日志
+        let mut input = match fs::File::open(&path) {
+            Ok(file) => file,
+            Err(e) => {
+                log::warn!("打包日志时无法读取 {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        let options = if name.ends_with(".gz") {
+            stored
+        } else {
+            deflated
+        };
+        zip.start_file(name, options)?;
+        io::copy(&mut input, &mut zip)?;
+        count += 1;
+    }
+
+    zip.finish()?.sync_all()?;
+    Ok(count)
+}
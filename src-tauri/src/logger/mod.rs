@@ -7,6 +7,7 @@ use std::path::PathBuf;
 use tauri::AppHandle;
 use tauri_plugin_store::StoreExt;
 
+pub mod export;
 pub mod rotate;
 
 /// 获取应用程序安装路径（或当前运行目录作为回退方案）
@@ -77,6 +78,22 @@ fn detect_log_level_from_settings(app_handle: &AppHandle) -> Option<LevelFilter>
         .and_then(|s| s.parse::<LevelFilter>().ok())
 }
 
+/// 从 settings.dat 读取日志文件的大小上限（MB）和每天保留的滚动文件数
+fn detect_log_rotation_from_settings(app_handle: &AppHandle) -> (u64, usize) {
+    let store = app_handle.store("settings.dat").ok();
+    let setting = |key: &str| {
+        store
+            .as_ref()
+            .and_then(|s| s.get(key))
+            .and_then(|v| v.as_u64())
+    };
+    let max_size_mb = setting("maxLogSizeMB").unwrap_or(rotate::DEFAULT_MAX_LOG_SIZE_MB);
+    let max_parts = setting("maxLogParts")
+        .map(|n| n as usize)
+        .unwrap_or(rotate::DEFAULT_MAX_LOG_PARTS);
+    (max_size_mb, max_parts)
+}
+
 /// 初始化带有日志滚动的 logging 系统
 pub fn setup_logging(app_handle: &AppHandle) -> Result<(), String> {
     // 使用 AppHandle 获取日志目录
@@ -90,15 +107,9 @@ pub fn setup_logging(app_handle: &AppHandle) -> Result<(), String> {
     // 初始化一次后，清除历史旧日志
     rotate::clean_old_logs(&log_dir);
 
-    // 根据当前日期构建日志文件名（每天一个）
-    let mut log_file_path = log_dir;
-    log_file_path.push(rotate::get_today_log_file_name());
-
-    // 打开文件追加写入
-    let log_file = std::fs::OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(log_file_path)
+    // 每天一个日志文件，超过大小上限时滚动为 YYYY-MM-DD.1.log 等
+    let (max_size_mb, max_parts) = detect_log_rotation_from_settings(app_handle);
+    let log_file = rotate::RollingLogWriter::new(log_dir, max_size_mb * 1024 * 1024, max_parts)
         .map_err(|e| e.to_string())?;
 
     // 从 settings.dat 检测级别 (优先级最高)
@@ -113,6 +124,7 @@ pub fn setup_logging(app_handle: &AppHandle) -> Result<(), String> {
     // 如果 level_from_settings 为 None，则回退到 level_from_files。
     let level = level_from_settings.unwrap_or(level_from_files);
 
+    // Dispatch 本身不过滤级别，实际级别由 log::set_max_level 控制，便于运行时调整
     Dispatch::new()
         .format(move |out, message, record| {
             out.finish(format_args!(
@@ -122,14 +134,28 @@ pub fn setup_logging(app_handle: &AppHandle) -> Result<(), String> {
                 message
             ))
         })
-        .level(level)
+        .level(LevelFilter::Trace)
         .chain(std::io::stdout())
-        .chain(log_file)
+        .chain(Box::new(log_file) as Box<dyn std::io::Write + Send>)
         .apply()
         .map_err(|e| e.to_string())?;
+    log::set_max_level(level);
 
     log::info!("日志模块加载成功");
     log::info!("当前日志级别为: {:?}", level);
+    log::info!(
+        "单个日志文件上限为: {} MB，每天最多保留 {} 个滚动文件",
+        max_size_mb,
+        max_parts
+    );
 
     Ok(())
 }
+
+/// 运行时修改日志级别，立即生效，无需重启程序
+pub fn set_log_level(level: LevelFilter) {
+    // 新级别低于 Info 时这条记录会被过滤，先临时放宽到 Info 再写入
+    log::set_max_level(level.max(LevelFilter::Info));
+    log::info!("日志级别已修改为: {:?}", level);
+    log::set_max_level(level);
+}
@@ -1,13 +1,125 @@
 use chrono::Local;
+use flate2::write::GzEncoder;
+use flate2::Compression;
 use std::fs;
-use std::path::PathBuf;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use tauri::{AppHandle, Manager};
 
 const MAX_LOG_KEEP_DAYS: i64 = 30; // 只保存最近30天日志
+pub const DEFAULT_MAX_LOG_SIZE_MB: u64 = 50; // 单个日志文件的默认大小上限（MB）
+pub const DEFAULT_MAX_LOG_PARTS: usize = 5; // 每天默认最多保留的滚动文件数
+
+fn today() -> String {
+    Local::now().format("%Y-%m-%d").to_string()
+}
 
 // 获取当日的日志文件名（如 logs/2025-04-05.log）
 pub fn get_today_log_file_name() -> String {
-    Local::now().format("%Y-%m-%d").to_string() + ".log"
+    today() + ".log"
+}
+
+// 获取滚动后的日志文件名（如 2025-04-05.1.log），序号越小越新
+fn part_log_file_name(date: &str, part: usize) -> String {
+    format!("{}.{}.log", date, part)
+}
+
+fn open_log_file(path: &Path) -> io::Result<(fs::File, u64)> {
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+    Ok((file, size))
+}
+
+/// 按天和文件大小滚动的日志写入器
+///
+/// 日期变化时切换到当天的日志文件；当天日志超过 `max_size` 字节时依次重命名为
+/// `YYYY-MM-DD.1.log`、`YYYY-MM-DD.2.log`……，最多保留 `max_parts` 个，更旧的直接删除。
+/// 滚动出的文件同样由 `clean_old_logs` 按30天清理。
+pub struct RollingLogWriter {
+    log_dir: PathBuf,
+    date: String,
+    file: fs::File,
+    size: u64,
+    max_size: u64, // 0 表示不限制大小
+    max_parts: usize,
+}
+
+impl RollingLogWriter {
+    pub fn new(log_dir: PathBuf, max_size: u64, max_parts: usize) -> io::Result<Self> {
+        let date = today();
+        let (file, size) = open_log_file(&log_dir.join(get_today_log_file_name()))?;
+        Ok(Self {
+            log_dir,
+            date,
+            file,
+            size,
+            max_size,
+            max_parts: max_parts.max(1),
+        })
+    }
+
+    /// 把当天日志依次后移一位，腾出当天的日志文件
+    fn shift_parts(&self) -> io::Result<()> {
+        let oldest = self
+            .log_dir
+            .join(part_log_file_name(&self.date, self.max_parts));
+        if oldest.exists() {
+            fs::remove_file(&oldest)?;
+        }
+        for part in (1..self.max_parts).rev() {
+            let from = self.log_dir.join(part_log_file_name(&self.date, part));
+            if from.exists() {
+                fs::rename(
+                    &from,
+                    self.log_dir.join(part_log_file_name(&self.date, part + 1)),
+                )?;
+            }
+        }
+        fs::rename(
+            self.log_dir.join(format!("{}.log", self.date)),
+            self.log_dir.join(part_log_file_name(&self.date, 1)),
+        )
+    }
+
+    /// 写入前检查是否需要切换文件
+    fn roll_if_needed(&mut self, incoming: usize) -> io::Result<()> {
+        let date = today();
+        let oversized =
+            self.max_size > 0 && self.size > 0 && self.size + incoming as u64 > self.max_size;
+        if date == self.date && !oversized {
+            return Ok(());
+        }
+
+        self.file.flush()?;
+        if date == self.date {
+            self.shift_parts()?;
+        } else {
+            self.date = date;
+        }
+        let (file, size) = open_log_file(&self.log_dir.join(format!("{}.log", self.date)))?;
+        self.file = file;
+        self.size = size;
+        Ok(())
+    }
+}
+
+impl Write for RollingLogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // 滚动失败时继续写入当前文件，不能因此丢失日志
+        if let Err(e) = self.roll_if_needed(buf.len()) {
+            eprintln!("日志文件滚动失败: {}", e);
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
 }
 
 // 获取 log 路径，同时创建目录（如果需要）
@@ -22,7 +134,7 @@ pub fn get_log_dir_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
     Ok(log_dir)
 }
 
-// 清除旧日志（大于30天前）
+// 清除旧日志（大于30天前），并压缩今天之前的日志
 pub fn clean_old_logs(log_dir: &PathBuf) {
     // 判断目录是否存在
     if !log_dir.exists() {
@@ -40,6 +152,7 @@ pub fn clean_old_logs(log_dir: &PathBuf) {
     };
 
     let now = Local::now();
+    let today = today();
 
     for entry in entries {
         let path = match entry {
@@ -72,7 +185,44 @@ pub fn clean_old_logs(log_dir: &PathBuf) {
             } else {
                 println!("已删除旧日志: {}", path.display());
             }
+            continue;
+        }
+
+        // 今天之前的日志不会再写入，压缩保存；当天的日志和滚动文件保持原样
+        let is_old_log = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.ends_with(".log") && !n.starts_with(&today));
+        if is_old_log {
+            match compress_log(&path) {
+                Ok(gz_path) => println!("已压缩旧日志: {}", gz_path.display()),
+                Err(e) => eprintln!("压缩失败 {}: {}", path.display(), e),
+            }
         }
     }
 }
 
+// 将日志压缩为同名 .log.gz 并删除原文件，压缩文件沿用原修改时间，保留期限不变
+fn compress_log(path: &Path) -> io::Result<PathBuf> {
+    let mut gz_path = path.as_os_str().to_owned();
+    gz_path.push(".gz");
+    let gz_path = PathBuf::from(gz_path);
+
+    let modified = fs::metadata(path)?.modified()?;
+    let result = (|| {
+        let mut input = fs::File::open(path)?;
+        let mut encoder = GzEncoder::new(fs::File::create(&gz_path)?, Compression::default());
+        io::copy(&mut input, &mut encoder)?;
+        let output = encoder.finish()?;
+        output.set_modified(modified)?;
+        output.sync_all()
+    })();
+    if let Err(e) = result {
+        // 压缩失败时保留原文件，删除不完整的压缩文件
+        fs::remove_file(&gz_path).ok();
+        return Err(e);
+    }
+    fs::remove_file(path)?;
+    Ok(gz_path)
+}
+
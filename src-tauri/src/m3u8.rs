@@ -0,0 +1,1094 @@
+//! M3U8 播放列表解析模块
+//! - 相对 URI 解析
+//! - 属性列表解析（支持引号内的逗号，如 CODECS="avc1.64001f,mp4a.40.2"）
+//! - 主播放列表（master playlist）的码率变体与音频/字幕轨道
+//! - 按画质选择码率变体
+//! - 媒体播放列表的分片、密钥（EXT-X-KEY）、字节范围和初始化分片
+
+use anyhow::{anyhow, Result};
+use base64::alphabet;
+use base64::engine::general_purpose::{GeneralPurpose, GeneralPurposeConfig};
+use base64::engine::DecodePaddingMode;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// 将播放列表中的 URI 解析为完整 URL
+///
+/// - 完整 URL 和内联的 `data:` URI 直接使用
+/// - 以 `/` 开头的绝对路径相对于域名根目录解析
+/// - 其余相对路径相对于播放列表所在目录解析
+///
+/// 按 URL 规范拼接，播放列表地址中的查询参数和片段不会被当作路径，`../` 和 `./` 会被规范化；
+/// 基准地址无法解析为 URL 时退回按字符串拼接。
+pub fn resolve_uri(base_url: &str, uri: &str) -> String {
+    if uri.starts_with("http") || is_data_uri(uri) {
+        return uri.to_string();
+    }
+    if let Some(joined) = reqwest::Url::parse(base_url)
+        .ok()
+        .and_then(|base| base.join(uri).ok())
+    {
+        return joined.to_string();
+    }
+
+    if uri.starts_with('/') {
+        let origin = base_url.split('/').take(3).collect::<Vec<&str>>().join("/");
+        format!("{}{}", origin, uri)
+    } else {
+        let dir = base_url.rsplit_once('/').map(|(dir, _)| dir).unwrap_or(base_url);
+        format!("{}/{}", dir, uri)
+    }
+}
+
+/// 解析标签的属性列表，如 `BANDWIDTH=1280000,CODECS="avc1,mp4a"`
+///
+/// 属性名统一为大写，值去掉两侧引号。
+pub fn parse_attribute_list(content: &str) -> HashMap<String, String> {
+    let mut attributes = HashMap::new();
+    let mut rest = content.trim();
+
+    while !rest.is_empty() {
+        let Some((name, after_eq)) = rest.split_once('=') else {
+            break;
+        };
+        let name = name.trim().to_uppercase();
+
+        let (value, remaining) = if let Some(quoted) = after_eq.strip_prefix('"') {
+            // 引号内的逗号属于值的一部分
+            match quoted.split_once('"') {
+                Some((value, after_quote)) => {
+                    let remaining = after_quote.split_once(',').map(|(_, r)| r).unwrap_or("");
+                    (value, remaining)
+                }
+                None => (quoted, ""),
+            }
+        } else {
+            after_eq.split_once(',').unwrap_or((after_eq, ""))
+        };
+
+        attributes.insert(name, value.trim().to_string());
+        rest = remaining.trim_start();
+    }
+    attributes
+}
+
+/// 是否为主播放列表（包含码率变体，而非分片列表）
+pub fn is_master_playlist(content: &str) -> bool {
+    content
+        .lines()
+        .any(|line| line.trim().starts_with("#EXT-X-STREAM-INF:"))
+}
+
+/// 码率变体（EXT-X-STREAM-INF）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoVariant {
+    pub uri: String,
+    pub bandwidth: Option<u64>,
+    pub average_bandwidth: Option<u64>,
+    /// 如 "1920x1080"
+    pub resolution: Option<String>,
+    pub frame_rate: Option<f64>,
+    pub codecs: Option<String>,
+    /// 关联的音频轨道组（对应 EXT-X-MEDIA 的 GROUP-ID）
+    pub audio_group: Option<String>,
+    /// 关联的字幕轨道组
+    pub subtitles_group: Option<String>,
+}
+
+/// 音频 / 字幕轨道（EXT-X-MEDIA）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Rendition {
+    pub group_id: String,
+    pub name: String,
+    pub language: Option<String>,
+    pub default: bool,
+    pub autoselect: bool,
+    /// 没有 URI 时该轨道已包含在变体流中
+    pub uri: Option<String>,
+}
+
+/// 主播放列表的全部轨道
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MasterPlaylist {
+    pub variants: Vec<VideoVariant>,
+    pub audio: Vec<Rendition>,
+    pub subtitles: Vec<Rendition>,
+}
+
+/// 解析主播放列表，URI 均解析为完整 URL
+pub fn parse_master_playlist(content: &str, base_url: &str) -> MasterPlaylist {
+    let mut playlist = MasterPlaylist::default();
+    // EXT-X-STREAM-INF 的属性作用于下一个非注释行（变体 URI）
+    let mut pending_variant: Option<HashMap<String, String>> = None;
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(attrs) = line.strip_prefix("#EXT-X-STREAM-INF:") {
+            pending_variant = Some(parse_attribute_list(attrs));
+        } else if let Some(attrs) = line.strip_prefix("#EXT-X-MEDIA:") {
+            let attrs = parse_attribute_list(attrs);
+            let rendition = Rendition {
+                group_id: attrs.get("GROUP-ID").cloned().unwrap_or_default(),
+                name: attrs.get("NAME").cloned().unwrap_or_default(),
+                language: attrs.get("LANGUAGE").cloned(),
+                default: attrs.get("DEFAULT").is_some_and(|v| v == "YES"),
+                autoselect: attrs.get("AUTOSELECT").is_some_and(|v| v == "YES"),
+                uri: attrs.get("URI").map(|uri| resolve_uri(base_url, uri)),
+            };
+            match attrs.get("TYPE").map(|t| t.as_str()) {
+                Some("AUDIO") => playlist.audio.push(rendition),
+                Some("SUBTITLES") => playlist.subtitles.push(rendition),
+                _ => {}
+            }
+        } else if !line.starts_with('#') {
+            if let Some(attrs) = pending_variant.take() {
+                playlist.variants.push(VideoVariant {
+                    uri: resolve_uri(base_url, line),
+                    bandwidth: attrs.get("BANDWIDTH").and_then(|v| v.parse().ok()),
+                    average_bandwidth: attrs
+                        .get("AVERAGE-BANDWIDTH")
+                        .and_then(|v| v.parse().ok()),
+                    resolution: attrs.get("RESOLUTION").cloned(),
+                    frame_rate: attrs.get("FRAME-RATE").and_then(|v| v.parse().ok()),
+                    codecs: attrs.get("CODECS").cloned(),
+                    audio_group: attrs.get("AUDIO").cloned(),
+                    subtitles_group: attrs.get("SUBTITLES").cloned(),
+                });
+            }
+        }
+    }
+
+    playlist
+}
+
+/// 播放列表的轨道信息
+///
+/// 序列化为 `{"kind": "master", "variants": [...], ...}` 或 `{"kind": "media"}`，
+/// 后者表示地址本身就是分片列表，没有可选的轨道。
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum PlaylistTracks {
+    Master(MasterPlaylist),
+    Media,
+}
+
+/// 根据播放列表内容列出轨道
+pub fn playlist_tracks(content: &str, base_url: &str) -> PlaylistTracks {
+    if is_master_playlist(content) {
+        PlaylistTracks::Master(parse_master_playlist(content, base_url))
+    } else {
+        PlaylistTracks::Media
+    }
+}
+
+/// 下载主播放列表时选择的画质
+///
+/// 前端传入字符串：`"highest"`（默认）、`"lowest"`，或指定分辨率，
+/// 如 `"1920x1080"`、`"1080p"`、`"1080"`（按画面高度匹配）。
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum VariantQuality {
+    #[default]
+    Highest,
+    Lowest,
+    /// 画面高度，如 1080
+    Height(u32),
+}
+
+impl TryFrom<String> for VariantQuality {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let value = value.trim().to_lowercase();
+        match value.as_str() {
+            "" | "highest" => return Ok(VariantQuality::Highest),
+            "lowest" => return Ok(VariantQuality::Lowest),
+            _ => {}
+        }
+        // "1920x1080" 取高度，"1080p" 去掉后缀
+        let height = value
+            .split_once('x')
+            .map(|(_, h)| h)
+            .unwrap_or(&value)
+            .trim_end_matches('p');
+        height
+            .parse()
+            .map(VariantQuality::Height)
+            .map_err(|_| format!("无效的画质: {}", value))
+    }
+}
+
+impl VideoVariant {
+    /// 从 RESOLUTION（如 "1920x1080"）中取画面高度
+    fn height(&self) -> Option<u32> {
+        self.resolution
+            .as_deref()
+            .and_then(|r| r.split_once('x'))
+            .and_then(|(_, h)| h.parse().ok())
+    }
+}
+
+/// 按画质选择码率变体，没有变体时返回 None
+///
+/// 指定分辨率时选择不超过该高度的最高画质，全部都更高时选择最低的一档；
+/// 同一分辨率有多个变体时选择码率较高的。
+pub fn select_variant<'a>(
+    playlist: &'a MasterPlaylist,
+    quality: &VariantQuality,
+) -> Option<&'a VideoVariant> {
+    let bandwidth = |v: &VideoVariant| v.bandwidth.unwrap_or(0);
+    let variants = playlist.variants.iter();
+    match quality {
+        VariantQuality::Highest => variants.max_by_key(|v| bandwidth(v)),
+        VariantQuality::Lowest => variants.min_by_key(|v| bandwidth(v)),
+        VariantQuality::Height(target) => variants
+            .clone()
+            .filter(|v| v.height().is_some_and(|h| h <= *target))
+            .max_by_key(|v| (v.height(), bandwidth(v)))
+            .or_else(|| variants.min_by_key(|v| bandwidth(v))),
+    }
+}
+
+/// 为码率变体选择单独下载的音频轨道
+///
+/// 只在变体 AUDIO 属性指定的组中选择：指定语言时优先选择该语言（按主标签匹配，
+/// 如 `en` 匹配 `en-US`，也可以填写轨道名称），否则依次选择 DEFAULT=YES、AUTOSELECT=YES
+/// 的轨道和组内第一个轨道。选中的轨道没有 URI 时音频已包含在变体流中，返回 None。
+pub fn select_audio_rendition<'a>(
+    playlist: &'a MasterPlaylist,
+    variant: &VideoVariant,
+    language: Option<&str>,
+) -> Option<&'a Rendition> {
+    let group = variant.audio_group.as_deref()?;
+    let renditions: Vec<&Rendition> = playlist
+        .audio
+        .iter()
+        .filter(|r| r.group_id == group)
+        .collect();
+    let primary_tag = |tag: &str| {
+        tag.split(['-', '_'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase()
+    };
+    let matches_language = |r: &Rendition, wanted: &str| {
+        r.name.eq_ignore_ascii_case(wanted)
+            || r.language.as_deref().is_some_and(|lang| {
+                lang.eq_ignore_ascii_case(wanted) || primary_tag(lang) == primary_tag(wanted)
+            })
+    };
+    let wanted = language.map(str::trim).filter(|l| !l.is_empty());
+    let selected = wanted
+        .and_then(|wanted| {
+            renditions
+                .iter()
+                .find(|r| matches_language(r, wanted))
+                .copied()
+        })
+        .or_else(|| renditions.iter().find(|r| r.default).copied())
+        .or_else(|| renditions.iter().find(|r| r.autoselect).copied())
+        .or_else(|| renditions.first().copied())?;
+    selected.uri.is_some().then_some(selected)
+}
+
+/// 分片加密方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EncryptionMethod {
+    /// 整个分片 AES-128-CBC 加密
+    #[default]
+    Aes128,
+    /// 只加密部分音视频采样（MPEG-TS）
+    SampleAes,
+}
+
+impl EncryptionMethod {
+    /// 按 EXT-X-KEY 的 METHOD 解析，NONE 返回 None，不支持的方式返回错误
+    pub fn parse(method: &str) -> Result<Option<Self>> {
+        match method.to_uppercase().as_str() {
+            "NONE" => Ok(None),
+            "AES-128" => Ok(Some(Self::Aes128)),
+            "SAMPLE-AES" => Ok(Some(Self::SampleAes)),
+            other => Err(anyhow!("暂不支持的加密方式: {}", other)),
+        }
+    }
+
+    /// EXT-X-KEY 中的 METHOD 名称
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Aes128 => "AES-128",
+            Self::SampleAes => "SAMPLE-AES",
+        }
+    }
+}
+
+/// 十六进制字符串转字节向量
+/// 示例：hex_to_bytes("0011ff") -> Ok(vec![0x00, 0x11, 0xff])
+fn hex_to_bytes(s: &str) -> Result<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return Err(anyhow::anyhow!("Hex string has odd length"));
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&s[i..i + 2], 16).map_err(|e| anyhow::anyhow!("Invalid hex: {}", e))
+        })
+        .collect()
+}
+
+/// 是否为内联数据的 `data:` URI（不区分大小写）
+pub fn is_data_uri(uri: &str) -> bool {
+    uri.get(..5)
+        .is_some_and(|scheme| scheme.eq_ignore_ascii_case("data:"))
+}
+
+/// 解码 `data:[<媒体类型>][;base64],<数据>` 形式的 URI
+///
+/// 部分播放列表把密钥直接内联在 EXT-X-KEY 的 URI 中：
+/// - 带 `;base64` 标记时按 base64 解码
+/// - 媒体类型含 `hex`，或数据以 `0x` 开头时按十六进制解码
+/// - 其余情况按百分号编码的原始字节处理
+pub fn decode_data_uri(uri: &str) -> Result<Vec<u8>> {
+    if !is_data_uri(uri) {
+        return Err(anyhow!("不是 data URI: {}", uri));
+    }
+    let (header, data) = uri[5..]
+        .split_once(',')
+        .ok_or_else(|| anyhow!("data URI 缺少数据部分"))?;
+    let params: Vec<String> = header
+        .split(';')
+        .map(|param| param.trim().to_ascii_lowercase())
+        .collect();
+    let data = percent_decode(data.trim());
+
+    if params.iter().any(|param| param == "base64") {
+        return base64_decode(&data);
+    }
+    let text = String::from_utf8_lossy(&data);
+    let text = text.trim();
+    let hex_prefixed = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X"));
+    if params[0].contains("hex") || hex_prefixed.is_some() {
+        return hex_to_bytes(hex_prefixed.unwrap_or(text));
+    }
+    Ok(data)
+}
+
+/// 解码 URI 中的百分号编码，无效的转义原样保留
+fn percent_decode(s: &str) -> Vec<u8> {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let value = s
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            if let Some(value) = value {
+                decoded.push(value);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    decoded
+}
+
+/// data URI 中的 base64 填充可有可无
+const BASE64_CONFIG: GeneralPurposeConfig =
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent);
+const BASE64_STANDARD: GeneralPurpose = GeneralPurpose::new(&alphabet::STANDARD, BASE64_CONFIG);
+const BASE64_URL_SAFE: GeneralPurpose = GeneralPurpose::new(&alphabet::URL_SAFE, BASE64_CONFIG);
+
+/// 标准 base64 解码，同时接受 URL 安全字母表，忽略空白，末尾的 `=` 填充可省略
+fn base64_decode(data: &[u8]) -> Result<Vec<u8>> {
+    let data: Vec<u8> = data
+        .iter()
+        .copied()
+        .filter(|c| !c.is_ascii_whitespace())
+        .collect();
+    let engine = if data.iter().any(|&c| c == b'-' || c == b'_') {
+        &BASE64_URL_SAFE
+    } else {
+        &BASE64_STANDARD
+    };
+    engine
+        .decode(&data)
+        .map_err(|e| anyhow!("无效的 base64 数据: {}", e))
+}
+
+/// IV 的长度（AES-128 的块长度）
+const IV_LENGTH: usize = 16;
+
+/// 规范化 IV 长度
+///
+/// 部分不规范的播放列表会给出较短的 IV（如 8 字节），按常见播放器的宽松处理在左侧补零；
+/// 超过 16 字节无法确定取哪一部分，直接报错。
+fn normalize_iv(iv: Vec<u8>) -> Result<Vec<u8>> {
+    match iv.len() {
+        IV_LENGTH => Ok(iv),
+        len if len < IV_LENGTH => {
+            log::warn!("IV 长度为 {} 字节，左侧补零至 {} 字节", len, IV_LENGTH);
+            let mut padded = vec![0u8; IV_LENGTH - len];
+            padded.extend(iv);
+            Ok(padded)
+        }
+        len => Err(anyhow!("IV 长度为 {} 字节，超过 {} 字节", len, IV_LENGTH)),
+    }
+}
+
+/// 解析M3U8的EXT-X-KEY标签
+/// 返回元组：(加密方法, 密钥URI, IV值)
+/// 示例输入："METHOD=AES-128,URI="key.php",IV=0X112233..."
+///
+/// 按属性列表解析，引号内的逗号（如 `data:` URI、KEYFORMATVERSIONS）不会被当作分隔符。
+fn parse_ext_x_key(line: &str) -> Result<(String, String, Option<String>)> {
+    let content = line.trim_start_matches("#EXT-X-KEY:").trim();
+    let mut attributes = parse_attribute_list(content);
+    let method = attributes
+        .remove("METHOD")
+        .ok_or_else(|| anyhow!("EXT-X-KEY 缺少 METHOD: {}", line))?;
+    let uri = attributes.remove("URI").unwrap_or_default();
+    let iv = attributes.remove("IV");
+    Ok((method, uri, iv))
+}
+
+/// EXT-X-KEY 指定的密钥，解析时尚未获取
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SegmentKey {
+    pub method: EncryptionMethod,
+    /// 密钥的完整地址
+    pub uri: String,
+    /// 播放列表中给出的 IV（已补齐到 16 字节），未给出时为 None
+    pub iv: Option<Vec<u8>>,
+}
+
+/// 解析 EXT-X-KEY 标签，METHOD=NONE 时返回 None（之后的分片不加密）
+pub fn parse_key_tag(line: &str, base_url: &str) -> Result<Option<SegmentKey>> {
+    let (method, key_uri, iv_str) = parse_ext_x_key(line)?;
+    // METHOD=NONE 表示之后的分片不再加密，返回 None 清除之前的密钥；
+    // 无法识别的加密方式直接报错，不会沿用之前的密钥解密后续分片
+    let Some(method) = EncryptionMethod::parse(&method)? else {
+        return Ok(None);
+    };
+    if key_uri.is_empty() {
+        return Err(anyhow!("EXT-X-KEY 缺少 URI: {}", line));
+    }
+    // 解析IV值（0x/0X 前缀均可），长度不足 16 字节时补零
+    let iv = match iv_str.as_ref() {
+        Some(iv_raw) => {
+            let hex = iv_raw
+                .strip_prefix("0x")
+                .or_else(|| iv_raw.strip_prefix("0X"))
+                .unwrap_or(iv_raw);
+            match hex_to_bytes(hex) {
+                Ok(bytes) => Some(normalize_iv(bytes)?),
+                Err(_) => None,
+            }
+        }
+        None => None,
+    };
+    Ok(Some(SegmentKey {
+        method,
+        uri: resolve_uri(base_url, &key_uri),
+        iv,
+    }))
+}
+
+/// 资源中的一段字节范围（BYTERANGE="长度@偏移"）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ByteRange {
+    pub length: u64,
+    pub offset: u64,
+}
+
+impl ByteRange {
+    /// 解析 `长度[@偏移]`，未给出偏移时从 0 开始
+    pub fn parse(value: &str) -> Result<Self> {
+        Self::parse_after(value, 0)
+    }
+
+    /// 解析 `长度[@偏移]`，未给出偏移时从 `next_offset` 开始（紧接上一个分片的字节范围）
+    pub fn parse_after(value: &str, next_offset: u64) -> Result<Self> {
+        let (length, offset) = match value.split_once('@') {
+            Some((length, offset)) => (length, Some(offset)),
+            None => (value, None),
+        };
+        let invalid = || anyhow!("无效的 BYTERANGE: {}", value);
+        Ok(Self {
+            length: length.trim().parse().map_err(|_| invalid())?,
+            offset: match offset {
+                Some(offset) => offset.trim().parse().map_err(|_| invalid())?,
+                None => next_offset,
+            },
+        })
+    }
+
+    /// 字节范围之后的第一个字节的位置
+    pub fn end(&self) -> u64 {
+        self.offset + self.length
+    }
+
+    /// HTTP Range 请求头的值
+    pub fn header_value(&self) -> String {
+        format!("bytes={}-{}", self.offset, self.end().saturating_sub(1))
+    }
+}
+
+/// 媒体播放列表中的分片
+#[derive(Debug, Clone)]
+pub struct MediaSegment {
+    /// 分片的完整地址
+    pub uri: String,
+    /// 媒体序号：EXT-X-MEDIA-SEQUENCE 加上分片在列表中的位置
+    pub sequence: u64,
+    /// #EXTINF 中的时长（秒）
+    pub duration: f64,
+    pub key: Option<SegmentKey>,
+    pub byte_range: Option<ByteRange>,
+    /// 分片前有 EXT-X-DISCONTINUITY，即新的不连续序列从该分片开始（如插入广告、编码切换）
+    pub discontinuity: bool,
+}
+
+/// fMP4 流的初始化分片（EXT-X-MAP）
+#[derive(Debug, Clone)]
+pub struct MediaInitSection {
+    pub uri: String,
+    pub byte_range: Option<ByteRange>,
+    /// 出现 EXT-X-MAP 时生效的密钥
+    pub key: Option<SegmentKey>,
+}
+
+/// 媒体播放列表的解析结果
+#[derive(Debug, Clone, Default)]
+pub struct ParsedPlaylist {
+    pub segments: Vec<MediaSegment>,
+    pub init: Option<MediaInitSection>,
+    /// 有 EXT-X-ENDLIST，分片列表不会再变化
+    pub ended: bool,
+    /// 有 EXT-X-MEDIA-SEQUENCE
+    pub has_media_sequence: bool,
+    /// EXT-X-TARGETDURATION（秒），直播流按此间隔刷新
+    pub target_duration: Option<f64>,
+}
+
+impl ParsedPlaylist {
+    /// 是否为直播流：没有 EXT-X-ENDLIST 且有 EXT-X-MEDIA-SEQUENCE，分片窗口会不断滑动
+    pub fn is_live(&self) -> bool {
+        !self.ended && self.has_media_sequence
+    }
+
+    /// 按 #EXTINF 累加的总时长（秒），没有时长信息时为 None
+    pub fn total_duration(&self) -> Option<f64> {
+        let total: f64 = self.segments.iter().map(|s| s.duration).sum();
+        (total > 0.0).then_some(total)
+    }
+
+    /// 不连续序列起始分片的下标（不含第一个分片）
+    pub fn discontinuities(&self) -> Vec<usize> {
+        self.segments
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| s.discontinuity)
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// 全部密钥，按在播放列表中出现的顺序
+    pub fn keys(&self) -> impl Iterator<Item = &SegmentKey> {
+        self.init
+            .iter()
+            .filter_map(|init| init.key.as_ref())
+            .chain(self.segments.iter().filter_map(|s| s.key.as_ref()))
+    }
+
+    /// 需要获取的密钥地址，相同地址只保留一个
+    pub fn key_uris(&self) -> Vec<String> {
+        let mut seen = HashSet::new();
+        self.keys()
+            .filter(|key| seen.insert(key.uri.as_str()))
+            .map(|key| key.uri.clone())
+            .collect()
+    }
+}
+
+/// 解析 EXT-X-MAP 标签，示例输入：`URI="init.mp4",BYTERANGE="720@0"`
+fn parse_map_tag(
+    attributes: &str,
+    base_url: &str,
+    key: Option<SegmentKey>,
+) -> Result<MediaInitSection> {
+    let attributes = parse_attribute_list(attributes);
+    let uri = attributes
+        .get("URI")
+        .ok_or_else(|| anyhow!("EXT-X-MAP 缺少 URI"))?;
+    Ok(MediaInitSection {
+        uri: resolve_uri(base_url, uri),
+        byte_range: attributes
+            .get("BYTERANGE")
+            .map(|r| ByteRange::parse(r))
+            .transpose()?,
+        key,
+    })
+}
+
+/// 解析媒体播放列表：分片地址、加密信息、媒体序号、字节范围和初始化分片
+///
+/// 只解析文本，不发起网络请求；相对地址相对于 `base_url` 解析。
+pub fn parse_playlist(base_url: &str, content: &str) -> Result<ParsedPlaylist> {
+    let mut playlist = ParsedPlaylist::default();
+    let mut current_key: Option<SegmentKey> = None;
+    let mut segment_duration = 0.0;
+
+    // 第一个分片的媒体序号（EXT-X-MEDIA-SEQUENCE），未指定时为 0
+    let mut media_sequence: u64 = 0;
+    // #EXTINF 之后的第一个非注释行即为分片 URI，不依赖扩展名（.m4s、.aac、带查询参数或无扩展名）
+    let mut expecting_segment = false;
+    // 下一个分片的 EXT-X-BYTERANGE，原样保存，遇到分片 URI 时再确定偏移
+    let mut pending_byte_range: Option<String> = None;
+    // 上一个字节范围分片的 (地址, 结束位置)，省略偏移时紧接其后
+    let mut last_range_end: Option<(String, u64)> = None;
+    // EXT-X-DISCONTINUITY 作用于下一个分片
+    let mut pending_discontinuity = false;
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with("#EXT-X-KEY:") {
+            current_key = parse_key_tag(line, base_url)?;
+            continue;
+        }
+
+        // 注意与 #EXT-X-DISCONTINUITY-SEQUENCE 区分
+        if line == "#EXT-X-DISCONTINUITY" {
+            pending_discontinuity = true;
+            continue;
+        }
+
+        if let Some(attributes) = line.strip_prefix("#EXT-X-MAP:") {
+            if playlist.init.is_some() {
+                log::warn!("播放列表包含多个 EXT-X-MAP，仅使用第一个");
+            } else {
+                playlist.init = Some(parse_map_tag(attributes, base_url, current_key.clone())?);
+            }
+            continue;
+        }
+
+        if let Some(sequence) = line.strip_prefix("#EXT-X-MEDIA-SEQUENCE:") {
+            media_sequence = sequence.trim().parse().unwrap_or_else(|_| {
+                log::warn!("无效的 EXT-X-MEDIA-SEQUENCE: {}", sequence);
+                0
+            });
+            playlist.has_media_sequence = true;
+            continue;
+        }
+
+        if let Some(duration) = line.strip_prefix("#EXT-X-TARGETDURATION:") {
+            playlist.target_duration = duration.trim().parse().ok();
+            continue;
+        }
+
+        if line == "#EXT-X-ENDLIST" {
+            playlist.ended = true;
+            continue;
+        }
+
+        // 示例："#EXT-X-BYTERANGE:75232@0"，偏移可省略
+        if let Some(range) = line.strip_prefix("#EXT-X-BYTERANGE:") {
+            pending_byte_range = Some(range.trim().to_string());
+            continue;
+        }
+
+        // 示例："#EXTINF:10.010,title"
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            segment_duration = info
+                .split(',')
+                .next()
+                .unwrap_or("")
+                .trim()
+                .parse()
+                .unwrap_or(0.0);
+            expecting_segment = true;
+            continue;
+        }
+
+        // 请求地址保留查询参数
+        if expecting_segment && !line.is_empty() && !line.starts_with('#') {
+            let uri = resolve_uri(base_url, line);
+            let byte_range = match pending_byte_range.take() {
+                Some(value) => {
+                    let next_offset = match &last_range_end {
+                        Some((prev_uri, end)) if *prev_uri == uri => *end,
+                        _ => 0,
+                    };
+                    let range = ByteRange::parse_after(&value, next_offset)?;
+                    last_range_end = Some((uri.clone(), range.end()));
+                    Some(range)
+                }
+                None => None,
+            };
+            playlist.segments.push(MediaSegment {
+                uri,
+                sequence: media_sequence + playlist.segments.len() as u64,
+                duration: segment_duration,
+                key: current_key.clone(),
+                byte_range,
+                // 第一个分片之前的不连续标记没有意义
+                discontinuity: pending_discontinuity && !playlist.segments.is_empty(),
+            });
+            pending_discontinuity = false;
+            expecting_segment = false;
+        }
+    }
+
+    Ok(playlist)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; 16] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+
+    #[test]
+    fn decodes_base64_data_uri_without_media_type() {
+        let key = decode_data_uri("data:;base64,AAECAwQFBgcICQoLDA0ODw==").unwrap();
+        assert_eq!(key, KEY);
+    }
+
+    #[test]
+    fn decodes_unpadded_and_url_safe_base64() {
+        assert_eq!(
+            decode_data_uri("data:text/plain;base64,AAECAwQFBgcICQoLDA0ODw").unwrap(),
+            KEY
+        );
+        assert_eq!(
+            decode_data_uri("data:;base64,-_-_").unwrap(),
+            [0xfb, 0xff, 0xbf]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_base64() {
+        assert!(decode_data_uri("data:;base64,AA*A").is_err());
+    }
+
+    #[test]
+    fn decodes_hex_and_percent_encoded_data_uri() {
+        assert_eq!(
+            decode_data_uri("data:text/hex,000102030405060708090a0b0c0d0e0f").unwrap(),
+            KEY
+        );
+        assert_eq!(
+            decode_data_uri("data:,0x000102030405060708090A0B0C0D0E0F").unwrap(),
+            KEY
+        );
+        assert_eq!(decode_data_uri("data:,a%20b").unwrap(), b"a b");
+    }
+
+    #[test]
+    fn key_tag_keeps_commas_inside_quoted_data_uri() {
+        let line = r#"#EXT-X-KEY:METHOD=AES-128,URI="data:text/plain;base64,AAECAwQFBgcICQoLDA0ODw==",IV=0x1"#;
+        let key = parse_key_tag(line, "https://example.com/live/index.m3u8")
+            .unwrap()
+            .unwrap();
+        assert_eq!(key.uri, "data:text/plain;base64,AAECAwQFBgcICQoLDA0ODw==");
+        assert_eq!(decode_data_uri(&key.uri).unwrap(), KEY);
+    }
+
+    const AUDIO_MASTER: &str = "#EXTM3U
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"English\",LANGUAGE=\"en-US\",DEFAULT=YES,AUTOSELECT=YES,URI=\"audio/en.m3u8\"
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"日本語\",LANGUAGE=\"ja\",AUTOSELECT=YES,URI=\"audio/ja.m3u8\"
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"muxed\",NAME=\"Main\",DEFAULT=YES
+#EXT-X-STREAM-INF:BANDWIDTH=2000000,AUDIO=\"aac\"
+video/720p.m3u8
+#EXT-X-STREAM-INF:BANDWIDTH=800000,AUDIO=\"muxed\"
+video/360p.m3u8
+#EXT-X-STREAM-INF:BANDWIDTH=500000
+video/240p.m3u8
+";
+
+    fn selected_audio(variant: usize, language: Option<&str>) -> Option<String> {
+        let master = parse_master_playlist(AUDIO_MASTER, "https://example.com/master.m3u8");
+        select_audio_rendition(&master, &master.variants[variant], language)
+            .and_then(|r| r.uri.clone())
+    }
+
+    #[test]
+    fn selects_default_audio_rendition() {
+        assert_eq!(
+            selected_audio(0, None).as_deref(),
+            Some("https://example.com/audio/en.m3u8")
+        );
+        // 空白的语言设置等同于未指定
+        assert_eq!(
+            selected_audio(0, Some("  ")).as_deref(),
+            Some("https://example.com/audio/en.m3u8")
+        );
+    }
+
+    #[test]
+    fn selects_audio_rendition_by_language_or_name() {
+        let ja = Some("https://example.com/audio/ja.m3u8");
+        assert_eq!(selected_audio(0, Some("ja-JP")).as_deref(), ja);
+        assert_eq!(selected_audio(0, Some("日本語")).as_deref(), ja);
+        // 没有匹配的语言时退回默认轨道
+        assert_eq!(
+            selected_audio(0, Some("fr")).as_deref(),
+            Some("https://example.com/audio/en.m3u8")
+        );
+    }
+
+    #[test]
+    fn audio_inside_variant_needs_no_separate_rendition() {
+        // 选中的轨道没有 URI，音频已包含在变体流中
+        assert_eq!(selected_audio(1, None), None);
+        // 变体没有 AUDIO 属性
+        assert_eq!(selected_audio(2, None), None);
+    }
+
+    const MEDIA_PLAYLIST: &str = "#EXTM3U
+#EXT-X-TARGETDURATION:10
+#EXT-X-MEDIA-SEQUENCE:7
+#EXTINF:10.0,
+https://cdn.example.com/abs/seg0.ts
+#EXT-X-KEY:METHOD=AES-128,URI=\"/keys/k1.bin\",IV=0x0102
+#EXTINF:9.5,
+seg1.ts?sig=abc
+#EXT-X-BYTERANGE:1000@500
+#EXTINF:4,
+/root/seg2.m4s
+#EXT-X-ENDLIST
+";
+
+    #[test]
+    fn parse_playlist_collects_segments_in_order() {
+        let playlist =
+            parse_playlist("https://example.com/live/index.m3u8", MEDIA_PLAYLIST).unwrap();
+        let uris: Vec<_> = playlist.segments.iter().map(|s| s.uri.as_str()).collect();
+        assert_eq!(
+            uris,
+            [
+                "https://cdn.example.com/abs/seg0.ts",
+                "https://example.com/live/seg1.ts?sig=abc",
+                "https://example.com/root/seg2.m4s",
+            ]
+        );
+        let sequences: Vec<_> = playlist.segments.iter().map(|s| s.sequence).collect();
+        assert_eq!(sequences, [7, 8, 9]);
+        assert_eq!(playlist.total_duration(), Some(23.5));
+        assert_eq!(
+            playlist.segments[2].byte_range,
+            Some(ByteRange {
+                length: 1000,
+                offset: 500
+            })
+        );
+        assert!(playlist.ended);
+        assert!(!playlist.is_live());
+        assert_eq!(playlist.target_duration, Some(10.0));
+    }
+
+    #[test]
+    fn parse_playlist_applies_key_to_following_segments() {
+        let playlist =
+            parse_playlist("https://example.com/live/index.m3u8", MEDIA_PLAYLIST).unwrap();
+        assert!(playlist.segments[0].key.is_none());
+        let key = playlist.segments[1].key.as_ref().unwrap();
+        assert_eq!(key.method, EncryptionMethod::Aes128);
+        assert_eq!(key.uri, "https://example.com/keys/k1.bin");
+        // 较短的 IV 左侧补零到 16 字节
+        let mut iv = vec![0u8; 14];
+        iv.extend([0x01, 0x02]);
+        assert_eq!(key.iv.as_deref(), Some(iv.as_slice()));
+        assert_eq!(playlist.segments[2].key.as_ref(), Some(key));
+        assert_eq!(playlist.key_uris(), ["https://example.com/keys/k1.bin"]);
+    }
+
+    #[test]
+    fn parses_key_tag_attributes() {
+        let key = parse_key_tag(
+            "#EXT-X-KEY:METHOD=SAMPLE-AES,URI=\"https://keys.example.com/k?id=1\",IV=0X000102030405060708090A0B0C0D0E0F,KEYFORMAT=\"identity\"",
+            "https://example.com/index.m3u8",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(key.method, EncryptionMethod::SampleAes);
+        assert_eq!(key.uri, "https://keys.example.com/k?id=1");
+        assert_eq!(key.iv.as_deref(), Some(KEY.as_slice()));
+
+        // 没有 IV 时按媒体序号计算，这里保持 None
+        let key = parse_key_tag(
+            "#EXT-X-KEY:METHOD=AES-128,URI=\"key.bin\"",
+            "https://example.com/a/index.m3u8",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(key.uri, "https://example.com/a/key.bin");
+        assert_eq!(key.iv, None);
+    }
+
+    #[test]
+    fn rejects_key_tag_without_uri_or_with_unknown_method() {
+        let base = "https://example.com/index.m3u8";
+        assert!(parse_key_tag("#EXT-X-KEY:METHOD=AES-128", base).is_err());
+        assert!(parse_key_tag("#EXT-X-KEY:METHOD=AES-256,URI=\"k\"", base).is_err());
+        assert!(parse_key_tag("#EXT-X-KEY:URI=\"k\"", base).is_err());
+    }
+
+    #[test]
+    fn resolves_relative_absolute_and_root_relative_uris() {
+        let base = "https://example.com/videos/show/index.m3u8";
+        assert_eq!(
+            resolve_uri(base, "seg.ts"),
+            "https://example.com/videos/show/seg.ts"
+        );
+        assert_eq!(resolve_uri(base, "/seg.ts"), "https://example.com/seg.ts");
+        assert_eq!(
+            resolve_uri(base, "https://cdn.example.com/seg.ts"),
+            "https://cdn.example.com/seg.ts"
+        );
+        assert_eq!(resolve_uri(base, "data:;base64,AAEC"), "data:;base64,AAEC");
+    }
+
+    #[test]
+    fn resolves_uris_against_playlist_url_with_query() {
+        let base = "https://example.com/path/index.m3u8?token=abc#t=1";
+        assert_eq!(
+            resolve_uri(base, "seg.ts"),
+            "https://example.com/path/seg.ts"
+        );
+        assert_eq!(
+            resolve_uri(base, "seg.ts?part=2"),
+            "https://example.com/path/seg.ts?part=2"
+        );
+        assert_eq!(resolve_uri(base, "/key.bin"), "https://example.com/key.bin");
+    }
+
+    #[test]
+    fn resolves_parent_and_current_directory_segments() {
+        let base = "https://example.com/a/b/index.m3u8?token=abc";
+        assert_eq!(
+            resolve_uri(base, "../media/seg.ts"),
+            "https://example.com/a/media/seg.ts"
+        );
+        assert_eq!(
+            resolve_uri(base, "./seg.ts"),
+            "https://example.com/a/b/seg.ts"
+        );
+        assert_eq!(
+            resolve_uri(base, "../../../seg.ts"),
+            "https://example.com/seg.ts"
+        );
+    }
+
+    #[test]
+    fn key_method_none_clears_encryption_for_following_segments() {
+        let content = "#EXTM3U
+#EXT-X-KEY:METHOD=AES-128,URI=\"key.bin\"
+#EXTINF:10,
+seg0.ts
+#EXTINF:10,
+seg1.ts
+#EXT-X-KEY:METHOD=NONE
+#EXTINF:10,
+seg2.ts
+#EXTINF:10,
+seg3.ts
+#EXT-X-ENDLIST
+";
+        let playlist = parse_playlist("https://example.com/index.m3u8", content).unwrap();
+        let encrypted: Vec<_> = playlist.segments.iter().map(|s| s.key.is_some()).collect();
+        assert_eq!(encrypted, [true, true, false, false]);
+        assert_eq!(playlist.key_uris(), ["https://example.com/key.bin"]);
+    }
+
+    #[test]
+    fn unknown_key_method_fails_instead_of_keeping_previous_key() {
+        let content = "#EXTM3U
+#EXT-X-KEY:METHOD=AES-128,URI=\"key.bin\"
+#EXTINF:10,
+seg0.ts
+#EXT-X-KEY:METHOD=AES-CTR,URI=\"key2.bin\"
+#EXTINF:10,
+seg1.ts
+";
+        assert!(parse_playlist("https://example.com/index.m3u8", content).is_err());
+    }
+
+    fn key_iv(iv: &str) -> Result<Option<Vec<u8>>> {
+        let line = format!("#EXT-X-KEY:METHOD=AES-128,URI=\"key.bin\",IV={}", iv);
+        let key = parse_key_tag(&line, "https://example.com/index.m3u8")?;
+        Ok(key.unwrap().iv)
+    }
+
+    #[test]
+    fn short_iv_is_left_padded() {
+        let mut expected = vec![0u8; 8];
+        expected.extend([1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(key_iv("0x0102030405060708").unwrap(), Some(expected));
+    }
+
+    #[test]
+    fn exact_iv_passes_through() {
+        assert_eq!(
+            key_iv("0x000102030405060708090a0b0c0d0e0f").unwrap(),
+            Some(KEY.to_vec())
+        );
+    }
+
+    #[test]
+    fn over_long_iv_is_rejected() {
+        assert!(key_iv("0x000102030405060708090a0b0c0d0e0f10").is_err());
+    }
+
+    #[test]
+    fn segments_are_detected_by_extinf_not_extension() {
+        let content = "#EXTM3U
+#EXTINF:6,
+seg1.ts?token=abc
+#EXTINF:6,
+#EXT-X-BYTERANGE:100@0
+video.m4s
+#EXTINF:6,
+audio/seg3.aac
+#EXTINF:6,
+https://cdn.example.com/segment/4
+
+#EXT-X-ENDLIST
+";
+        let playlist = parse_playlist("https://example.com/v/index.m3u8", content).unwrap();
+        let uris: Vec<_> = playlist.segments.iter().map(|s| s.uri.as_str()).collect();
+        // 请求地址保留查询参数
+        assert_eq!(
+            uris,
+            [
+                "https://example.com/v/seg1.ts?token=abc",
+                "https://example.com/v/video.m4s",
+                "https://example.com/v/audio/seg3.aac",
+                "https://cdn.example.com/segment/4",
+            ]
+        );
+    }
+
+    #[test]
+    fn lines_without_extinf_are_not_segments() {
+        let content = "#EXTM3U
+stray.ts
+#EXTINF:6,
+seg1.ts
+orphan.ts
+#EXT-X-ENDLIST
+";
+        let playlist = parse_playlist("https://example.com/index.m3u8", content).unwrap();
+        assert_eq!(playlist.segments.len(), 1);
+        assert_eq!(playlist.segments[0].uri, "https://example.com/seg1.ts");
+    }
+}
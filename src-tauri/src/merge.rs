@@ -1,9 +1,18 @@
+use crate::download_manager::DownloadControl;
+use crate::notification::notify_task_completed;
+use crate::task_events::{
+    emit_task_event, emit_task_event_with, legacy_events_enabled, TaskPhase, TaskUpdate,
+};
 use anyhow::Result;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use std::sync::Arc;
 use tauri::path::BaseDirectory;
-use tauri::{AppHandle, Emitter, Manager};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_store::StoreExt;
 use tokio::fs::File;
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::process;
 
 /// 根据当前平台和架构，从 Tauri 资源中解析 ffmpeg 可执行文件的绝对路径。
@@ -72,22 +81,732 @@ pub async fn resolve_ffmpeg_path_and_prepare(handle: &AppHandle) -> Result<PathB
     }
 }
 
+/// ffmpeg 来源
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum FfmpegSource {
+    /// 应用自带的 ffmpeg
+    #[default]
+    Bundled,
+    /// 系统中的 ffmpeg：指定可执行文件路径，None 时在 PATH 中查找
+    System(Option<PathBuf>),
+}
+
+impl FfmpegSource {
+    /// 按 settings.dat 中的 `useSystemFfmpeg` 与 `ffmpegPath` 确定来源
+    pub fn from_settings(handle: &AppHandle) -> Self {
+        let Ok(store) = handle.store("settings.dat") else {
+            return FfmpegSource::Bundled;
+        };
+        let use_system = store
+            .get("useSystemFfmpeg")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        if !use_system {
+            return FfmpegSource::Bundled;
+        }
+        let path = store
+            .get("ffmpegPath")
+            .and_then(|v| v.as_str().map(|s| s.trim().to_string()))
+            .filter(|p| !p.is_empty())
+            .map(PathBuf::from);
+        FfmpegSource::System(path)
+    }
+}
+
+/// 文件存在且可执行
+fn is_executable(path: &std::path::Path) -> bool {
+    let Ok(metadata) = std::fs::metadata(path) else {
+        return false;
+    };
+    #[cfg(not(target_os = "windows"))]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+    }
+    #[cfg(target_os = "windows")]
+    {
+        metadata.is_file()
+    }
+}
+
+/// 在 PATH 中查找 ffmpeg
+fn find_ffmpeg_in_path() -> Option<PathBuf> {
+    #[cfg(target_os = "windows")]
+    let binary = "ffmpeg.exe";
+    #[cfg(not(target_os = "windows"))]
+    let binary = "ffmpeg";
+
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths)
+        .map(|dir| dir.join(binary))
+        .find(|candidate| is_executable(candidate))
+}
+
+/// 按来源解析 ffmpeg 路径
+///
+/// 系统 ffmpeg 不存在或不可执行时记录警告，退回使用自带的 ffmpeg。
+pub async fn resolve_ffmpeg(handle: &AppHandle, source: &FfmpegSource) -> Result<PathBuf> {
+    if let FfmpegSource::System(custom) = source {
+        let found = match custom {
+            Some(path) if is_executable(path) => Some(path.clone()),
+            Some(path) => {
+                log::warn!("指定的 ffmpeg 不存在或不可执行: {}", path.display());
+                None
+            }
+            None => {
+                let found = find_ffmpeg_in_path();
+                if found.is_none() {
+                    log::warn!("PATH 中未找到 ffmpeg");
+                }
+                found
+            }
+        };
+        match found {
+            Some(path) => {
+                log::info!("使用系统 ffmpeg: {}", path.display());
+                return Ok(path);
+            }
+            None => log::warn!("系统 ffmpeg 不可用，改用自带的 ffmpeg"),
+        }
+    }
+    resolve_ffmpeg_path_and_prepare(handle).await
+}
+
 /// 创建带平台特性的 Command
 #[cfg(target_os = "windows")]
-fn create_ffmpeg_command(ffmpeg: &str) -> process::Command {
+pub fn create_ffmpeg_command(ffmpeg: &str) -> process::Command {
     let mut cmd = process::Command::new(ffmpeg);
     cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
     cmd
 }
 
 #[cfg(not(target_os = "windows"))]
-fn create_ffmpeg_command(ffmpeg: &str) -> process::Command {
+pub fn create_ffmpeg_command(ffmpeg: &str) -> process::Command {
     process::Command::new(ffmpeg)
 }
 
+/// 合并选项
+#[derive(Debug, Clone)]
+pub struct MergeOptions {
+    /// 分片编码无法直接封装进 MP4 时自动重新编码；为 false 时直接报错
+    pub auto_reencode: bool,
+    /// concat 列表使用相对于临时目录的路径，不传 `-safe 0`
+    pub safe_concat: bool,
+    /// 合并前先把连续分片按组拼接成较大的中间文件；None 时分片数超过阈值自动开启
+    pub pre_concat: Option<bool>,
+    /// 不连续序列（EXT-X-DISCONTINUITY）起始分片的下标，预拼接时不会跨越这些边界
+    pub discontinuities: Vec<usize>,
+    /// 时间戳处理方式
+    pub timestamp_mode: TimestampMode,
+    /// `ts_files` 的第一个文件是 fMP4 初始化分片（EXT-X-MAP）
+    pub init_segment: bool,
+    /// 播放列表中 #EXTINF 时长之和（秒），用于计算合并进度；未知时按样本分片估算
+    pub total_duration: Option<f64>,
+    /// 输出容器格式
+    pub output_format: OutputFormat,
+    /// 使用的 ffmpeg
+    pub ffmpeg: FfmpegSource,
+    /// ffmpeg 读取网络输入时使用的代理和请求头
+    pub network: FfmpegNetwork,
+    /// 输出文件已存在时覆盖；为 false 时在文件名后追加序号
+    pub overwrite: bool,
+    /// 合并完成后截取一帧作为封面，保存为输出文件旁的同名 `.jpg`
+    pub generate_thumbnail: bool,
+    /// 单独下载的音频轨道，与视频一起封装
+    pub audio: Option<AudioTrack>,
+    /// 合并成功后保留的临时目录，路径随合并成功事件发给前端
+    pub kept_temp_dir: Option<String>,
+}
+
+/// 单独下载的音频轨道（主播放列表中 EXT-X-MEDIA TYPE=AUDIO 的轨道）
+///
+/// 合并时作为 ffmpeg 的第二路输入，与视频流一起封装到输出文件中。
+#[derive(Debug, Clone)]
+pub struct AudioTrack {
+    /// 音频分片所在的目录，音频的 concat 列表和编码探测缓存也写在这里
+    pub temp_dir: String,
+    /// 按顺序排列的音频分片，有初始化分片时排在最前面
+    pub files: Vec<String>,
+    /// `files` 的第一个文件是 fMP4 初始化分片
+    pub init_segment: bool,
+}
+
+/// ffmpeg 读取网络输入（如远程的 EXT-X-MAP 初始化分片）时使用的代理和请求头
+///
+/// 与下载分片时使用的配置一致。输入全部为本地文件时不追加任何参数。
+#[derive(Debug, Clone, Default)]
+pub struct FfmpegNetwork {
+    /// 代理地址；ffmpeg 的 http 协议只支持 HTTP 代理
+    pub proxy: Option<String>,
+    /// 请求头（名称, 值），含 User-Agent
+    pub headers: Vec<(String, String)>,
+}
+
+impl FfmpegNetwork {
+    /// 输入中有 http/https 地址时返回需要放在 `-i` 之前的参数
+    fn input_args<S: AsRef<str>>(&self, inputs: &[S]) -> Vec<String> {
+        if !inputs.iter().any(|input| is_network_input(input.as_ref())) {
+            return Vec::new();
+        }
+        // concat 列表中的远程地址需要显式允许网络协议
+        let mut args = vec![
+            "-protocol_whitelist".to_string(),
+            "file,http,https,tcp,tls,crypto".to_string(),
+        ];
+        if !self.headers.is_empty() {
+            let headers: String = self
+                .headers
+                .iter()
+                .map(|(name, value)| format!("{}: {}\r\n", name, value))
+                .collect();
+            args.extend(["-headers".to_string(), headers]);
+        }
+        match self.proxy.as_deref() {
+            Some(proxy) if proxy.to_lowercase().starts_with("http") => {
+                args.extend(["-http_proxy".to_string(), proxy.to_string()]);
+            }
+            Some(proxy) => log::warn!("ffmpeg 不支持该代理，网络输入将直接连接: {}", proxy),
+            None => {}
+        }
+        args
+    }
+}
+
+/// 输入是否为 http/https 地址
+fn is_network_input(input: &str) -> bool {
+    let lower = input.to_lowercase();
+    lower.starts_with("http://") || lower.starts_with("https://")
+}
+
+/// 输出容器格式
+///
+/// - `Mp4`：兼容性最好，部分编码需要重新编码才能封装
+/// - `Mkv`：几乎可以直接封装任何编码，时间戳不规范的流也更容易合并成功
+/// - `Ts`：TS 分片直接按字节拼接，不经过 ffmpeg；fMP4 分片仍由 ffmpeg 转封装
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum OutputFormat {
+    #[default]
+    Mp4,
+    Mkv,
+    Ts,
+}
+
+impl TryFrom<String> for OutputFormat {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.trim().to_lowercase().as_str() {
+            "" | "mp4" => Ok(OutputFormat::Mp4),
+            "mkv" => Ok(OutputFormat::Mkv),
+            "ts" => Ok(OutputFormat::Ts),
+            _ => Err(format!("不支持的输出格式: {}（可选 mp4、mkv、ts）", value)),
+        }
+    }
+}
+
+impl OutputFormat {
+    /// 输出文件的扩展名
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Mp4 => "mp4",
+            OutputFormat::Mkv => "mkv",
+            OutputFormat::Ts => "ts",
+        }
+    }
+}
+
+/// 合并时的时间戳处理方式
+///
+/// - `Off`：不加额外参数，由 concat 分离器按分片时长拼接时间戳，适合大多数流
+/// - `GenPts`：`-fflags +genpts`，为缺失 PTS 的包重新生成时间戳，可缓解长流的音画逐渐不同步；
+///   对本身时间戳完整的流基本无影响，但在 B 帧较多的流上偶尔会导致卡顿
+/// - `CopyTs`：`-copyts`，保留分片中的原始时间戳，不做重新计算；
+///   源时间戳连续时可避免累积误差，但遇到时间戳回绕或跳变（如插播广告）时输出会出现空白或无法播放
+/// - `Auto`：估算时长超过 [`LONG_STREAM_SECONDS`] 或流中有不连续边界时使用 `GenPts`，否则 `Off`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampMode {
+    #[default]
+    Auto,
+    Off,
+    GenPts,
+    CopyTs,
+}
+
+/// 超过该时长（秒）的流在 Auto 模式下重新生成时间戳
+const LONG_STREAM_SECONDS: f64 = 2.0 * 3600.0;
+
+impl TimestampMode {
+    /// 解析 Auto 模式，`estimated_duration` 为估算的总时长（秒）
+    ///
+    /// 有不连续边界时各段的时间戳互不衔接，直接复制容易因时间戳跳变失败，同样重新生成时间戳
+    fn resolve(self, estimated_duration: Option<f64>, discontinuous: bool) -> Self {
+        match self {
+            TimestampMode::Auto => match estimated_duration {
+                _ if discontinuous => TimestampMode::GenPts,
+                Some(d) if d > LONG_STREAM_SECONDS => TimestampMode::GenPts,
+                _ => TimestampMode::Off,
+            },
+            mode => mode,
+        }
+    }
+
+    /// 需要放在输入文件之前的 ffmpeg 参数
+    fn input_args(self) -> &'static [&'static str] {
+        match self {
+            TimestampMode::GenPts => &["-fflags", "+genpts"],
+            TimestampMode::CopyTs => &["-copyts"],
+            TimestampMode::Auto | TimestampMode::Off => &[],
+        }
+    }
+}
+
+/// 估算合并后的总时长（秒），用于选择 Auto 时间戳模式和计算合并进度
+///
+/// 优先使用播放列表给出的总时长（按实际合并的分片计算）；没有时用探测样本（第一个分片）的时长
+/// 乘以分片数 `segment_count`（不含初始化分片）粗略估算。点播流的分片时长通常相近，
+/// 误差足以判断是否超过 [`LONG_STREAM_SECONDS`]，但不能用来校验输出时长。
+fn estimate_duration(
+    total_duration: Option<f64>,
+    sample_duration: Option<f64>,
+    segment_count: usize,
+) -> Option<f64> {
+    total_duration.or_else(|| sample_duration.map(|d| d * segment_count as f64))
+}
+
+/// 自动开启预拼接的分片数阈值
+const PRE_CONCAT_AUTO_THRESHOLD: usize = 5000;
+/// 预拼接时每组包含的分片数
+const PRE_CONCAT_GROUP_SIZE: usize = 100;
+/// 同时拼接的组数
+const PRE_CONCAT_PARALLELISM: usize = 8;
+
+fn should_pre_concat(options: &MergeOptions, segment_count: usize) -> bool {
+    options
+        .pre_concat
+        .unwrap_or(segment_count > PRE_CONCAT_AUTO_THRESHOLD)
+}
+
+/// 按组大小和不连续边界把分片切分为若干连续区间
+fn group_segments(
+    segment_count: usize,
+    group_size: usize,
+    discontinuities: &[usize],
+) -> Vec<std::ops::Range<usize>> {
+    let mut groups = Vec::new();
+    let mut start = 0;
+    for index in 1..=segment_count {
+        if index == segment_count
+            || index - start >= group_size
+            || discontinuities.contains(&index)
+        {
+            groups.push(start..index);
+            start = index;
+        }
+    }
+    groups
+}
+
+/// 把一组连续的 TS 分片按字节顺序拼接到一个中间文件
+///
+/// 逐个打开输入文件，复制完即关闭，任一时刻只占用一个输入文件句柄。
+pub(crate) async fn concat_segment_group(inputs: &[String], output: &str) -> Result<()> {
+    let mut output_file = File::create(output).await?;
+    for input in inputs {
+        let mut input_file = File::open(input).await?;
+        tokio::io::copy(&mut input_file, &mut output_file).await?;
+    }
+    output_file.flush().await?;
+    Ok(())
+}
+
+/// 合并前的预拼接：并行地把每组分片拼接为 `merge_group_{n}.ts`，返回中间文件列表
+///
+/// TS 是流式格式，同一不连续序列内的分片直接按字节拼接即可播放，
+/// 分组不会跨越不连续边界，时间戳跳变仍交给 ffmpeg 的 concat 处理。
+pub(crate) async fn pre_concat_segments(
+    id: &str,
+    ts_files: &[String],
+    temp_dir: &str,
+    discontinuities: &[usize],
+    control: &DownloadControl,
+) -> Result<Vec<String>> {
+    let groups = group_segments(ts_files.len(), PRE_CONCAT_GROUP_SIZE, discontinuities);
+    log::info!(
+        "任务 [{}] 分片数 {}，预拼接为 {} 个中间文件",
+        id,
+        ts_files.len(),
+        groups.len()
+    );
+    let start = std::time::Instant::now();
+
+    let results: Vec<Result<String>> = futures::stream::iter(groups.into_iter().enumerate())
+        .map(|(n, range)| async move {
+            if control.is_cancelled() {
+                return Err(anyhow::anyhow!("任务已取消"));
+            }
+            let output = format!("{}/merge_group_{}.ts", temp_dir, n);
+            concat_segment_group(&ts_files[range], &output).await?;
+            Ok(output)
+        })
+        .buffered(PRE_CONCAT_PARALLELISM)
+        .collect()
+        .await;
+    let group_files = results.into_iter().collect::<Result<Vec<_>>>()?;
+
+    log::info!("任务 [{}] 预拼接完成，耗时 {:?}", id, start.elapsed());
+    Ok(group_files)
+}
+
+/// ffmpeg 在安全模式下接受的文件名：仅含字母、数字、`_`、`-`、`.`，且不以 `.` 开头
+fn is_concat_safe_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+/// 生成 concat 列表中的路径
+///
+/// 安全模式下返回相对于临时目录的文件名（ffmpeg 按列表文件所在目录解析相对路径），
+/// 不满足安全模式要求时返回 None，由调用方回退到绝对路径。
+fn relative_concat_entries(ts_files: &[String], temp_dir: &str) -> Option<Vec<String>> {
+    ts_files
+        .iter()
+        .map(|f| {
+            let relative = std::path::Path::new(f).strip_prefix(temp_dir).ok()?;
+            let name = relative.to_str()?;
+            is_concat_safe_name(name).then(|| name.to_string())
+        })
+        .collect()
+}
+
+/// 写入 ffconcat 列表，返回是否使用了安全模式（列表中只有相对路径）
+pub(crate) async fn write_concat_list(
+    id: &str,
+    list_path: &str,
+    files: &[String],
+    temp_dir: &str,
+    safe_concat: bool,
+) -> Result<bool> {
+    let relative_entries = if safe_concat {
+        let entries = relative_concat_entries(files, temp_dir);
+        if entries.is_none() {
+            log::warn!("{} 分片路径不满足 concat 安全模式要求，改用绝对路径", id);
+        }
+        entries
+    } else {
+        None
+    };
+    let safe_mode = relative_entries.is_some();
+    let entries = relative_entries.unwrap_or_else(|| files.to_vec());
+
+    let mut concat_file = File::create(list_path).await?;
+    // 显式声明格式，避免 ffmpeg 按内容猜测
+    concat_file.write_all(b"ffconcat version 1.0\n").await?;
+    for file in &entries {
+        concat_file
+            .write_all(concat_file_line(file).as_bytes())
+            .await?;
+    }
+    concat_file.flush().await?;
+    Ok(safe_mode)
+}
+
+/// 生成 concat 列表中的一行
+///
+/// ffmpeg 解析单引号内的内容时不处理任何转义，路径中的单引号需先闭合引号、
+/// 以 `\'` 输出后再重新打开，即 `'` 写为 `'\''`；反斜杠在引号内原样保留。
+fn concat_file_line(path: &str) -> String {
+    format!("file '{}'\n", path.replace('\'', r"'\''"))
+}
+
+/// MP4 容器可直接 `-c copy` 封装的视频编码
+const MP4_VIDEO_CODECS: &[&str] = &["h264", "hevc", "av1", "vp9", "mpeg4", "mpeg2video"];
+/// MP4 容器可直接 `-c copy` 封装的音频编码
+const MP4_AUDIO_CODECS: &[&str] = &["aac", "mp3", "ac3", "eac3", "opus", "alac", "flac"];
+
+/// 分片的编码探测结果
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CodecProbe {
+    pub video_codecs: Vec<String>,
+    pub audio_codecs: Vec<String>,
+    /// 样本分片的时长（秒），用于估算整个流的时长
+    #[serde(default)]
+    pub segment_duration: Option<f64>,
+}
+
+impl CodecProbe {
+    /// 无法直接封装进 MP4 的视频编码
+    fn incompatible_video(&self) -> Vec<&str> {
+        self.video_codecs
+            .iter()
+            .map(|c| c.as_str())
+            .filter(|c| !MP4_VIDEO_CODECS.contains(c))
+            .collect()
+    }
+
+    /// 无法直接封装进 MP4 的音频编码
+    fn incompatible_audio(&self) -> Vec<&str> {
+        self.audio_codecs
+            .iter()
+            .map(|c| c.as_str())
+            .filter(|c| !MP4_AUDIO_CODECS.contains(c))
+            .collect()
+    }
+}
+
+/// 解析 ffmpeg 输出的时间，如 "00:01:06.50"
+fn parse_timestamp(value: &str) -> Option<f64> {
+    let mut parts = value.split(':');
+    let hours: f64 = parts.next()?.parse().ok()?;
+    let minutes: f64 = parts.next()?.parse().ok()?;
+    let seconds: f64 = parts.next()?.parse().ok()?;
+    Some(hours * 3600.0 + minutes * 60.0 + seconds)
+}
+
+/// 解析 `ffmpeg -i` 输出中的时长
+/// 示例行："Duration: 00:00:06.00, start: 1.400000, bitrate: 1215 kb/s"
+fn parse_duration(line: &str) -> Option<f64> {
+    parse_timestamp(line.strip_prefix("Duration: ")?.split(',').next()?.trim())
+}
+
+/// 解析 ffmpeg 进度行中已处理的时长
+/// 示例行："frame=  240 fps=0.0 q=-1.0 size=    1024kB time=00:00:10.00 bitrate= 838.9kbits/s"
+fn parse_progress_time(line: &str) -> Option<f64> {
+    parse_timestamp(line.split(" time=").nth(1)?.split_whitespace().next()?)
+}
+
+/// 从 `ffmpeg -i` 的输出中解析流编码和时长
+/// 示例行："Stream #0:1[0x101](und): Audio: aac (LC) ([15][0][0][0] / 0x000F), 44100 Hz"
+fn parse_stream_codecs(output: &str) -> CodecProbe {
+    let mut probe = CodecProbe::default();
+    for line in output.lines() {
+        let line = line.trim();
+        if line.starts_with("Duration: ") {
+            probe.segment_duration = parse_duration(line);
+            continue;
+        }
+        if !line.starts_with("Stream #") {
+            continue;
+        }
+        for (marker, codecs) in [
+            ("Video: ", &mut probe.video_codecs),
+            ("Audio: ", &mut probe.audio_codecs),
+        ] {
+            if let Some(pos) = line.find(marker) {
+                let codec: String = line[pos + marker.len()..]
+                    .chars()
+                    .take_while(|c| !c.is_whitespace() && *c != ',')
+                    .collect();
+                if !codec.is_empty() {
+                    codecs.push(codec);
+                }
+            }
+        }
+    }
+    probe
+}
+
+/// 用 ffmpeg 探测样本分片的编码，结果缓存到临时目录的 probe.json
+async fn probe_codecs(
+    ffmpeg: &str,
+    sample: &str,
+    temp_dir: &str,
+    network: &FfmpegNetwork,
+) -> Result<CodecProbe> {
+    let cache_path = format!("{}/probe.json", temp_dir);
+    if let Ok(content) = tokio::fs::read_to_string(&cache_path).await {
+        if let Ok(probe) = serde_json::from_str::<CodecProbe>(&content) {
+            log::debug!("使用缓存的编码探测结果: {:?}", probe);
+            return Ok(probe);
+        }
+    }
+
+    // ffmpeg -i 不指定输出时会以非零状态退出，只解析 stderr 即可
+    let output = create_ffmpeg_command(ffmpeg)
+        .arg("-hide_banner")
+        .args(network.input_args(&[sample]))
+        .args(["-i", sample])
+        .output()
+        .await?;
+    let probe = parse_stream_codecs(&String::from_utf8_lossy(&output.stderr));
+    log::info!("分片编码探测结果: {:?}", probe);
+
+    if let Ok(json) = serde_json::to_string(&probe) {
+        tokio::fs::write(&cache_path, json).await.ok();
+    }
+    Ok(probe)
+}
+
+/// 用 `ffmpeg -i` 读取本地文件的编码和时长
+async fn probe_local_media(ffmpeg: &str, path: &str) -> Result<CodecProbe> {
+    let output = create_ffmpeg_command(ffmpeg)
+        .args(["-hide_banner", "-i", path])
+        .output()
+        .await?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Ok(parse_stream_codecs(&stderr))
+}
+
+/// 输出时长与播放列表总时长允许的最小偏差（秒）
+const DURATION_TOLERANCE_SECS: f64 = 2.0;
+/// 输出时长与播放列表总时长允许的相对偏差
+const DURATION_TOLERANCE_RATIO: f64 = 0.05;
+
+/// 校验合并后的输出：能读到音视频流，且时长与 #EXTINF 之和基本一致
+///
+/// `-c copy` 在不连续的时间戳等情况下可能“成功”输出只有几秒的文件，
+/// 校验不通过时只发出警告，不影响合并结果。
+async fn verify_output(
+    app_handle: &AppHandle,
+    id: &str,
+    ffmpeg: &str,
+    output_file_str: &str,
+    expected_duration: Option<f64>,
+) {
+    let probe = match probe_local_media(ffmpeg, output_file_str).await {
+        Ok(probe) => probe,
+        Err(e) => {
+            log::warn!("任务 [{}] 校验输出文件失败: {}", id, e);
+            return;
+        }
+    };
+    let stream_count = probe.video_codecs.len() + probe.audio_codecs.len();
+    let actual_duration = probe.segment_duration;
+    log::info!(
+        "任务 [{}] 输出文件: {} 个音视频流，时长 {:?} 秒（播放列表 {:?} 秒）",
+        id,
+        stream_count,
+        actual_duration,
+        expected_duration
+    );
+
+    let message = if stream_count == 0 {
+        Some("合并后的文件中没有可识别的音视频流，文件可能已损坏".to_string())
+    } else {
+        match (actual_duration, expected_duration) {
+            (Some(actual), Some(expected)) if expected > 0.0 => {
+                let tolerance = (expected * DURATION_TOLERANCE_RATIO).max(DURATION_TOLERANCE_SECS);
+                ((actual - expected).abs() > tolerance).then(|| {
+                    format!(
+                        "合并后的视频时长 {:.1} 秒与播放列表的 {:.1} 秒相差较大，文件可能不完整或无法正常播放",
+                        actual, expected
+                    )
+                })
+            }
+            _ => None,
+        }
+    };
+    let Some(message) = message else {
+        return;
+    };
+
+    log::warn!("任务 [{}] {}", id, message);
+    let mut update = TaskUpdate::new(id, TaskPhase::Merging, 4, message.clone());
+    update.details = Some(serde_json::json!({
+        "verification": {
+            "streams": stream_count,
+            "duration": actual_duration,
+            "expected_duration": expected_duration,
+        }
+    }));
+    emit_task_event(
+        app_handle,
+        "merge_warning",
+        serde_json::json!({
+            "id": id,
+            "message": &message,
+        }),
+        update,
+    )
+    .ok();
+}
+
+/// 合并进度事件的最小间隔
+const MERGE_PROGRESS_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+/// 合并失败时记录的 ffmpeg 输出行数
+const FFMPEG_ERROR_TAIL_LINES: usize = 10;
+
+/// 读取 ffmpeg 的 stderr，按进度行中的 `time=` 上报合并进度
+///
+/// 总时长未知时进度为 null（不确定进度），只上报已处理的时长。
+/// 返回最后几行非进度输出，合并失败时用于记录原因。
+async fn report_merge_progress(
+    id: String,
+    app_handle: AppHandle,
+    mut stderr: process::ChildStderr,
+    total_duration: Option<f64>,
+) -> Vec<String> {
+    let mut tail = std::collections::VecDeque::with_capacity(FFMPEG_ERROR_TAIL_LINES);
+    let mut pending = Vec::new();
+    let mut buffer = [0u8; 4096];
+    let mut last_emit: Option<std::time::Instant> = None;
+    let mut last_progress = None;
+    // 合并期间会多次发送进度，兼容开关只读取一次
+    let legacy_events = legacy_events_enabled(&app_handle);
+
+    // 进度行以 \r 结尾，其余输出以 \n 结尾
+    while let Ok(read) = stderr.read(&mut buffer).await {
+        if read == 0 {
+            break;
+        }
+        pending.extend_from_slice(&buffer[..read]);
+        while let Some(pos) = pending.iter().position(|&b| b == b'\r' || b == b'\n') {
+            let line: Vec<u8> = pending.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line).trim().to_string();
+            if line.is_empty() {
+                continue;
+            }
+            let Some(time) = parse_progress_time(&line) else {
+                if tail.len() == FFMPEG_ERROR_TAIL_LINES {
+                    tail.pop_front();
+                }
+                tail.push_back(line);
+                continue;
+            };
+
+            let progress = total_duration
+                .filter(|&total| total > 0.0)
+                .map(|total| ((time / total * 100.0).floor() as u32).min(99));
+            let due = last_emit
+                .map(|t| t.elapsed() >= MERGE_PROGRESS_INTERVAL)
+                .unwrap_or(true);
+            if !due || (progress.is_some() && progress == last_progress) {
+                continue;
+            }
+            last_emit = Some(std::time::Instant::now());
+            last_progress = progress;
+
+            let mut update = TaskUpdate::new(&id, TaskPhase::Merging, 4, "合并中");
+            update.progress = progress;
+            update.details = Some(serde_json::json!({ "time": time, "duration": total_duration }));
+            emit_task_event_with(
+                &app_handle,
+                legacy_events,
+                "merge_video",
+                serde_json::json!({
+                    "id": &id,
+                    "isMerged": false,
+                    "status": 4,
+                    "message": "合并中",
+                    "mergeProgress": progress,
+                    "mergedSeconds": time,
+                }),
+                update,
+            )
+            .ok();
+        }
+    }
+    tail.into()
+}
+
 /// 合并失败统一处理
 async fn fail_merge(app_handle: &AppHandle, id: &str) -> Result<()> {
-    app_handle.emit(
+    emit_task_event(
+        app_handle,
         "merge_video",
         serde_json::json!({
             "id": id,
@@ -95,57 +814,70 @@ async fn fail_merge(app_handle: &AppHandle, id: &str) -> Result<()> {
             "status": 400,
             "message": "合并失败"
         }),
+        TaskUpdate::new(id, TaskPhase::Error, 400, "合并失败"),
     )?;
     Ok(())
 }
 
+/// 合并被取消时通知前端，与合并失败区分
+fn cancel_merge(app_handle: &AppHandle, id: &str) -> Result<()> {
+    emit_task_event(
+        app_handle,
+        "merge_video",
+        serde_json::json!({
+            "id": id,
+            "isMerged": false,
+            "status": 0,
+            "message": "合并已取消"
+        }),
+        TaskUpdate::new(id, TaskPhase::Cancelled, 0, "合并已取消"),
+    )?;
+    Ok(())
+}
+
+/// 文件名（不含扩展名）的最大字节数，多数文件系统限制单个文件名为 255 字节，
+/// 预留扩展名和重名时追加的序号
+const MAX_FILENAME_BYTES: usize = 200;
+
 /// 移除文件名中的非法字符，以确保文件名在操作系统层面合法。
 fn sanitize_filename(name: &str) -> String {
     // Windows 文件系统不允许的字符集： \ / : * ? " < > |
+    // 其他平台只禁止 / 和 \0，但统一按 Windows 处理，保证文件可以在各平台之间拷贝
     let illegal_chars = r#"\/*:?"<>|"#;
 
-    // 此外，Windows 不允许以空格或点开头/结尾
-    // 也不允许使用 CON, PRN, AUX, NUL, COMx, LPTx 作为文件名（无论大小写）
-
-    // 移除非法字符
+    // 移除非法字符和控制字符（含换行、制表符）
     let sanitized: String = name
         .chars()
-        .filter(|c| !illegal_chars.contains(*c))
+        .filter(|c| !illegal_chars.contains(*c) && !c.is_control())
         .collect();
 
-    // 移除开头/结尾的空格或点
+    // Windows 不允许以空格或点结尾，以点开头在其他平台上会成为隐藏文件
+    let sanitized = sanitized.trim_matches(|c: char| c == '.' || c.is_whitespace());
+
+    // 过长的文件名按字符边界截断
+    let mut end = sanitized.len().min(MAX_FILENAME_BYTES);
+    while !sanitized.is_char_boundary(end) {
+        end -= 1;
+    }
     #[allow(unused_mut)]
-    let mut sanitized = sanitized.trim_matches(|c| c == ' ' || c == '.').to_string();
+    let mut sanitized = sanitized[..end].trim_end().to_string();
 
-    // 检查 Windows 保留名（虽然大部分都会被前面的非法字符和 trim 处理，但为了健壮性保留）
+    // Windows 保留文件名（无论大小写，也不论是否带扩展名）：CON, PRN, AUX, NUL, COM1-9, LPT1-9
     #[cfg(target_os = "windows")]
     {
-        // Windows 保留文件名（无论大小写，无扩展名时）
-        let reserved_names = [
-            "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7",
-            "COM8", "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
-        ];
-
-        // 获取文件名（不含路径）和扩展名
-        let name_path = std::path::Path::new(&sanitized);
-        if let Some(file_name) = name_path.file_name().and_then(|n| n.to_str()) {
-            let name_upper = file_name.to_uppercase();
-            // 检查无扩展名的部分
-            if let Some(name_no_ext) = name_upper.split('.').next() {
-                if reserved_names
-                    .iter()
-                    .any(|&reserved| reserved == name_no_ext)
-                {
-                    // 如果匹配保留名，则在文件名后附加一个下划线
-                    if let Some(ext) = name_path.extension().and_then(|e| e.to_str()) {
-                        // 有扩展名
-                        sanitized = format!("{}_{}.{}", name_no_ext, "_", ext);
-                    } else {
-                        // 无扩展名
-                        sanitized = format!("{}_", name_no_ext);
-                    }
-                }
-            }
+        let stem = sanitized
+            .split('.')
+            .next()
+            .unwrap_or_default()
+            .to_uppercase();
+        let reserved = matches!(stem.as_str(), "CON" | "PRN" | "AUX" | "NUL")
+            || ((stem.starts_with("COM") || stem.starts_with("LPT"))
+                && stem.len() == 4
+                && matches!(stem.as_bytes()[3], b'1'..=b'9'));
+        if reserved {
+            // 在保留名后附加一个下划线，保留原有的大小写和其余部分
+            let stem_len = stem.len();
+            sanitized.insert(stem_len, '_');
         }
     }
 
@@ -157,7 +889,32 @@ fn sanitize_filename(name: &str) -> String {
     }
 }
 
+/// 构建输出文件路径
+///
+/// 目标文件已存在且不允许覆盖时，在文件名后追加 " (2)"、" (3)"……，直到找到未被占用的路径。
+fn resolve_output_path(output_dir: &str, name: &str, extension: &str, overwrite: bool) -> PathBuf {
+    let dir = std::path::Path::new(output_dir);
+    let path = dir.join(format!("{}.{}", name, extension));
+    if overwrite || !path.exists() {
+        return path;
+    }
+    (2..)
+        .map(|n| dir.join(format!("{} ({}).{}", name, n, extension)))
+        .find(|candidate| !candidate.exists())
+        .unwrap_or(path)
+}
+
 /// 使用ffmpeg合并ts
+///
+/// `ts_files` 必须已按播放列表顺序排列（取自 segments.json 的分片列表），这里不再按文件名排序，
+/// concat.txt 按传入的顺序写入。
+///
+/// 合并过程中任务被取消时，终止 ffmpeg 进程并删除不完整的输出文件，返回 Ok(())，
+/// 与下载阶段的取消处理一致。
+///
+/// 分片数量没有硬性上限：分片路径只写入 concat.txt，不会放进命令行参数，
+/// ffmpeg 的 concat 分离器按顺序逐个打开、读完即关闭分片，任一时刻只持有一个分片的文件句柄，
+/// 几万个分片也不会触及文件描述符限制。
 pub async fn merge_files(
     id: String,
     name: &str,
@@ -165,34 +922,30 @@ pub async fn merge_files(
     temp_dir: &str,
     output_dir: &str,
     app_handle: AppHandle,
+    control: Arc<DownloadControl>,
+    options: MergeOptions,
 ) -> Result<()> {
-    // 1. 创建 concat.txt
-    let concat_file_path = format!("{}/concat.txt", temp_dir);
-    let mut concat_file = File::create(&concat_file_path).await?;
-    
-    for ts_file in &ts_files {
-        // 正确转义单引号
-        let escaped = ts_file.replace('\\', r"\\").replace('\'', r"\'");
-        concat_file
-            .write_all(format!("file '{}'\n", escaped).as_bytes())
-            .await?;
-    }
-    concat_file.flush().await?;
-    drop(concat_file);
-
-    // 2. 净化文件名并构建输出路径
+    // 1. 净化文件名并构建输出路径，同名文件已存在时按设置覆盖或追加序号
     let sanitized_name = sanitize_filename(name);
-    let output_path = std::path::Path::new(output_dir).join(format!("{}.mp4", sanitized_name));
+    if sanitized_name != name {
+        log::info!(
+            "任务 [{}] 文件名 {:?} 已净化为 {:?}",
+            id,
+            name,
+            sanitized_name
+        );
+    }
+    let output_path = resolve_output_path(
+        output_dir,
+        &sanitized_name,
+        options.output_format.extension(),
+        options.overwrite,
+    );
     let output_file_str = output_path.to_string_lossy();
 
-    // 3. 获取 ffmpeg
-    let ffmpeg_path = resolve_ffmpeg_path_and_prepare(&app_handle).await?;
-    let ffmpeg = ffmpeg_path
-        .to_str()
-        .ok_or_else(|| anyhow::anyhow!("ffmpeg 路径无效"))?;
-
     // 通知开始
-    app_handle.emit(
+    emit_task_event(
+        &app_handle,
         "start_merge_video",
         serde_json::json!({
             "id": &id,
@@ -200,37 +953,565 @@ pub async fn merge_files(
             "status": 4,
             "message": "开始合并"
         }),
+        TaskUpdate::new(&id, TaskPhase::Merging, 4, "开始合并"),
     )?;
 
-    let status = create_ffmpeg_command(ffmpeg)
-        .args([
-            "-y",
-            "-f",
-            "concat",
-            "-safe",
-            "0",
-            "-i",
-            &concat_file_path,
-            "-c",
-            "copy",
-            "-map",
-            "0",
-            "-avoid_negative_ts",
-            "make_zero",
-            "-bsf:a",
-            "aac_adtstoasc",
+    // TS 分片输出为 TS 时直接按字节拼接，不经过 ffmpeg；有单独的音频轨道时需要 ffmpeg 封装
+    if options.output_format == OutputFormat::Ts && !options.init_segment && options.audio.is_none()
+    {
+        log::info!("任务 [{}] 按字节拼接 {} 个 TS 分片", id, ts_files.len());
+        // 拼接本身不需要 ffmpeg，只用于校验输出和生成封面
+        let ffmpeg_path = resolve_ffmpeg(&app_handle, &options.ffmpeg)
+            .await
+            .map_err(|e| log::warn!("任务 [{}] 获取 ffmpeg 失败，跳过校验和封面: {}", id, e))
+            .ok();
+        let ffmpeg = ffmpeg_path.as_deref().and_then(|p| p.to_str());
+        if !concat_to_output(&app_handle, &id, &ts_files, &output_path, &control).await? {
+            return Ok(());
+        }
+        if let Some(ffmpeg) = ffmpeg {
+            verify_output(
+                &app_handle,
+                &id,
+                ffmpeg,
+                &output_file_str,
+                options.total_duration,
+            )
+            .await;
+        }
+        return complete_merge(
+            &app_handle,
+            &id,
             &output_file_str,
-        ])
-        .status()
-        .await?;
+            ffmpeg.filter(|_| options.generate_thumbnail),
+            options.kept_temp_dir.as_deref(),
+        )
+        .await;
+    }
+
+    // 2. 获取 ffmpeg，不可用时退回按字节拼接
+    let ffmpeg_path = match resolve_ffmpeg(&app_handle, &options.ffmpeg).await {
+        Ok(path) => path,
+        Err(e) => {
+            log::error!("任务 [{}] 获取 ffmpeg 失败: {}", id, e);
+            return fallback_merge(
+                &app_handle,
+                &id,
+                &ts_files,
+                &fallback_output_path(output_dir, &sanitized_name, &options),
+                &options,
+                &control,
+                None,
+            )
+            .await;
+        }
+    };
+    let ffmpeg = ffmpeg_path
+        .to_str()
+        .ok_or_else(|| anyhow::anyhow!("ffmpeg 路径无效"))?;
+
+    // 预先探测编码，判断能否直接封装进 MP4；ffmpeg 无法运行时同样退回按字节拼接
+    let audio_sample = options
+        .audio
+        .as_ref()
+        .and_then(|audio| Some((audio.files.first()?, audio.temp_dir.as_str())));
+    let audio_probe = match audio_sample {
+        Some((sample, dir)) => match probe_codecs(ffmpeg, sample, dir, &options.network).await {
+            Ok(probe) => Some(probe),
+            Err(e) => {
+                log::error!("任务 [{}] 探测音频编码失败: {}", id, e);
+                return fallback_merge(
+                    &app_handle,
+                    &id,
+                    &ts_files,
+                    &fallback_output_path(output_dir, &sanitized_name, &options),
+                    &options,
+                    &control,
+                    Some(ffmpeg),
+                )
+                .await;
+            }
+        },
+        None => None,
+    };
+    let mut codec_args: Vec<&str> = vec!["-c", "copy"];
+    let mut estimated_duration = None;
+    if let Some(sample) = ts_files.first() {
+        let probe = match probe_codecs(ffmpeg, sample, temp_dir, &options.network).await {
+            Ok(probe) => probe,
+            Err(e) => {
+                log::error!("任务 [{}] 探测编码失败: {}", id, e);
+                return fallback_merge(
+                    &app_handle,
+                    &id,
+                    &ts_files,
+                    &fallback_output_path(output_dir, &sanitized_name, &options),
+                    &options,
+                    &control,
+                    Some(ffmpeg),
+                )
+                .await;
+            }
+        };
+        let segment_count = ts_files.len() - usize::from(options.init_segment);
+        estimated_duration = estimate_duration(
+            options.total_duration,
+            probe.segment_duration,
+            segment_count,
+        );
+        let bad_video = probe.incompatible_video();
+        let mut bad_audio = probe.incompatible_audio();
+        if let Some(audio_probe) = &audio_probe {
+            bad_audio.extend(audio_probe.incompatible_audio());
+        }
+        // 只有 MP4 对编码有限制，MKV 和 TS 直接复制
+        let mp4 = options.output_format == OutputFormat::Mp4;
+        if mp4 && (!bad_video.is_empty() || !bad_audio.is_empty()) {
+            let codecs = [bad_video.as_slice(), bad_audio.as_slice()].concat().join(", ");
+            if !options.auto_reencode {
+                fail_merge(&app_handle, &id).await?;
+                return Err(anyhow::anyhow!(
+                    "此流的编码需要重新编码（{} 无法直接封装为 MP4），请开启自动重新编码后重试",
+                    codecs
+                ));
+            }
+
+            log::warn!("{} 编码 {} 无法直接封装为 MP4，改为重新编码", id, codecs);
+            let message = format!(
+                "编码 {} 无法直接封装为 MP4，已自动切换为重新编码，合并会变慢",
+                codecs
+            );
+            emit_task_event(
+                &app_handle,
+                "merge_warning",
+                serde_json::json!({
+                    "id": &id,
+                    "message": &message,
+                }),
+                TaskUpdate::new(&id, TaskPhase::Merging, 4, message.clone()),
+            )?;
+            // 只重新编码不兼容的轨道，其余保持复制
+            codec_args = vec![
+                "-c:v",
+                if bad_video.is_empty() { "copy" } else { "libx264" },
+                "-c:a",
+                if bad_audio.is_empty() { "copy" } else { "aac" },
+            ];
+        }
+    }
+
+    // 3. fMP4 分片单独无法解析，需与初始化分片按字节拼接为一个完整文件；
+    //    分片极多时先按组拼接，减少 ffmpeg 逐个打开文件的开销
+    let ts_files = if options.init_segment {
+        let joined = format!("{}/merge_fmp4.mp4", temp_dir);
+        log::info!("任务 [{}] 拼接初始化分片与 {} 个 fMP4 分片", id, ts_files.len() - 1);
+        concat_segment_group(&ts_files, &joined).await?;
+        vec![joined]
+    } else if should_pre_concat(&options, ts_files.len()) {
+        let discontinuities = &options.discontinuities;
+        match pre_concat_segments(&id, &ts_files, temp_dir, discontinuities, &control).await {
+            Err(_) if control.is_cancelled() => {
+                log::info!("任务 [{}] 已取消，停止预拼接", id);
+                return cancel_merge(&app_handle, &id);
+            }
+            result => result?,
+        }
+    } else {
+        ts_files
+    };
+
+    // 4. 创建 concat.txt
+    let concat_file_path = format!("{}/concat.txt", temp_dir);
+    let safe_mode = write_concat_list(
+        &id,
+        &concat_file_path,
+        &ts_files,
+        temp_dir,
+        options.safe_concat,
+    )
+    .await?;
+
+    // 单独的音频轨道同样生成 concat 列表，fMP4 音频先与初始化分片拼接为一个文件
+    let audio_input = match &options.audio {
+        Some(audio) => {
+            let audio_files = if audio.init_segment {
+                let joined = format!("{}/merge_audio.mp4", audio.temp_dir);
+                concat_segment_group(&audio.files, &joined).await?;
+                vec![joined]
+            } else {
+                audio.files.clone()
+            };
+            let list_path = format!("{}/concat.txt", audio.temp_dir);
+            let audio_safe = write_concat_list(
+                &id,
+                &list_path,
+                &audio_files,
+                &audio.temp_dir,
+                options.safe_concat,
+            )
+            .await?;
+            log::info!(
+                "任务 [{}] 合并单独的音频轨道（{} 个文件）",
+                id,
+                audio_files.len()
+            );
+            Some((list_path, audio_safe))
+        }
+        None => None,
+    };
+
+    // 当前分片都已下载到本地，只有列表中有远程地址时才追加代理和请求头
+    let network_args = options.network.input_args(&ts_files);
+    let discontinuous = !options.discontinuities.is_empty();
+    let timestamp_mode = options
+        .timestamp_mode
+        .resolve(estimated_duration, discontinuous);
+    if discontinuous {
+        let message = if timestamp_mode == TimestampMode::GenPts {
+            format!(
+                "流中有 {} 处不连续（如插入广告、编码切换），合并时将重新生成时间戳",
+                options.discontinuities.len()
+            )
+        } else {
+            format!(
+                "流中有 {} 处不连续（如插入广告、编码切换），当前时间戳处理方式可能导致合并失败",
+                options.discontinuities.len()
+            )
+        };
+        log::warn!("任务 [{}] {}", id, message);
+        emit_task_event(
+            &app_handle,
+            "merge_warning",
+            serde_json::json!({
+                "id": &id,
+                "message": &message,
+            }),
+            TaskUpdate::new(&id, TaskPhase::Merging, 4, message.clone()),
+        )?;
+    }
+    if timestamp_mode != TimestampMode::Off {
+        log::info!(
+            "任务 [{}] 时间戳处理: {:?} (估算时长 {:?} 秒)",
+            id,
+            timestamp_mode,
+            estimated_duration
+        );
+    }
+    let args = mux_args(&MuxInputs {
+        video_list: (&concat_file_path, safe_mode),
+        audio_list: audio_input
+            .as_ref()
+            .map(|(list, safe)| (list.as_str(), *safe)),
+        timestamp_mode,
+        network_args: &network_args,
+        codec_args: &codec_args,
+        output_format: options.output_format,
+        output: &output_file_str,
+    });
+
+    // 编码探测和预拼接可能耗时较长，启动 ffmpeg 前再检查一次
+    if control.is_cancelled() {
+        log::info!("任务 [{}] 已取消，跳过合并", id);
+        return cancel_merge(&app_handle, &id);
+    }
+
+    let spawned = create_ffmpeg_command(ffmpeg)
+        .args(&args)
+        .stderr(std::process::Stdio::piped())
+        .spawn();
+    let mut child = match spawned {
+        Ok(child) => child,
+        Err(e) => {
+            log::error!("任务 [{}] 启动 ffmpeg 失败: {}", id, e);
+            return fallback_merge(
+                &app_handle,
+                &id,
+                &ts_files,
+                &fallback_output_path(output_dir, &sanitized_name, &options),
+                &options,
+                &control,
+                Some(ffmpeg),
+            )
+            .await;
+        }
+    };
+    let progress_handle = child.stderr.take().map(|stderr| {
+        tokio::spawn(report_merge_progress(
+            id.clone(),
+            app_handle.clone(),
+            stderr,
+            estimated_duration,
+        ))
+    });
+    let Some(status) = wait_ffmpeg(&id, &mut child, &control, &output_path).await? else {
+        if let Some(handle) = &progress_handle {
+            handle.abort();
+        }
+        return cancel_merge(&app_handle, &id);
+    };
+
+    // 进程退出后 stderr 随之关闭，读取任务会很快结束
+    let ffmpeg_output = match progress_handle {
+        Some(handle) => handle.await.unwrap_or_default(),
+        None => Vec::new(),
+    };
 
     if !status.success() {
-        fail_merge(&app_handle, &id).await?;
-        return Err(anyhow::anyhow!("FFmpeg 合并失败"));
+        log::error!("任务 [{}] ffmpeg 输出:\n{}", id, ffmpeg_output.join("\n"));
+        // 删除 ffmpeg 留下的不完整输出，改为按字节拼接
+        tokio::fs::remove_file(&output_path).await.ok();
+        return fallback_merge(
+            &app_handle,
+            &id,
+            &ts_files,
+            &fallback_output_path(output_dir, &sanitized_name, &options),
+            &options,
+            &control,
+            Some(ffmpeg),
+        )
+        .await;
+    }
+
+    verify_output(
+        &app_handle,
+        &id,
+        ffmpeg,
+        &output_file_str,
+        options.total_duration,
+    )
+    .await;
+
+    let thumbnail_ffmpeg = options.generate_thumbnail.then_some(ffmpeg);
+    complete_merge(
+        &app_handle,
+        &id,
+        &output_file_str,
+        thumbnail_ffmpeg,
+        options.kept_temp_dir.as_deref(),
+    )
+    .await
+}
+
+/// 等待 ffmpeg 进程退出
+///
+/// 等待期间任务被取消时终止进程并删除不完整的输出文件，返回 None。
+async fn wait_ffmpeg(
+    id: &str,
+    child: &mut process::Child,
+    control: &DownloadControl,
+    output_path: &std::path::Path,
+) -> Result<Option<std::process::ExitStatus>> {
+    tokio::select! {
+        status = child.wait() => Ok(Some(status?)),
+        _ = control.cancelled() => {
+            // kill 会等待进程退出，确保输出文件不再被占用
+            if let Err(e) = child.kill().await {
+                log::warn!("任务 [{}] 终止 ffmpeg 失败: {}", id, e);
+            }
+            tokio::fs::remove_file(output_path).await.ok();
+            log::info!("任务 [{}] 合并已取消，已删除不完整的输出文件", id);
+            Ok(None)
+        }
     }
+}
 
-    // 成功
-    app_handle.emit(
+/// 合并命令的输入和输出
+struct MuxInputs<'a> {
+    /// 视频分片的 concat 列表，以及列表是否只含相对路径（安全模式）
+    video_list: (&'a str, bool),
+    /// 单独音频轨道的 concat 列表，同上
+    audio_list: Option<(&'a str, bool)>,
+    timestamp_mode: TimestampMode,
+    /// 代理、请求头等网络参数，放在第一个输入之前
+    network_args: &'a [String],
+    codec_args: &'a [&'a str],
+    output_format: OutputFormat,
+    output: &'a str,
+}
+
+/// 构建 ffmpeg 合并命令的参数
+///
+/// 有单独的音频轨道时作为第二个输入，取第一个输入的全部流和第二个输入的音频流。
+fn mux_args<'a>(inputs: &MuxInputs<'a>) -> Vec<&'a str> {
+    let (video_list, video_safe) = inputs.video_list;
+    // 安全模式下列表中只有相对路径，无需 -safe 0
+    let mut args: Vec<&str> = vec!["-y", "-f", "concat"];
+    if !video_safe {
+        args.extend(["-safe", "0"]);
+    }
+    args.extend(inputs.timestamp_mode.input_args());
+    args.extend(inputs.network_args.iter().map(String::as_str));
+    args.extend(["-i", video_list]);
+    if let Some((audio_list, audio_safe)) = inputs.audio_list {
+        args.extend(["-f", "concat"]);
+        if !audio_safe {
+            args.extend(["-safe", "0"]);
+        }
+        args.extend(inputs.timestamp_mode.input_args());
+        args.extend(["-i", audio_list]);
+    }
+    args.extend(inputs.codec_args);
+    args.extend(["-map", "0"]);
+    if inputs.audio_list.is_some() {
+        args.extend(["-map", "1:a"]);
+    }
+    args.extend(["-avoid_negative_ts", "make_zero"]);
+    // TS 中的 AAC 使用 ADTS 封装，只有 MP4/MKV 需要转换
+    if inputs.output_format != OutputFormat::Ts {
+        args.extend(["-bsf:a", "aac_adtstoasc"]);
+    }
+    args.push(inputs.output);
+    args
+}
+
+/// 按字节拼接分片到输出文件，支持取消
+///
+/// 返回 false 表示已取消（已删除不完整的输出并通知前端），调用方不再继续。
+async fn concat_to_output(
+    app_handle: &AppHandle,
+    id: &str,
+    files: &[String],
+    output_path: &std::path::Path,
+    control: &DownloadControl,
+) -> Result<bool> {
+    let output_file_str = output_path.to_string_lossy();
+    let result = tokio::select! {
+        result = concat_segment_group(files, &output_file_str) => result,
+        _ = control.cancelled() => {
+            tokio::fs::remove_file(output_path).await.ok();
+            log::info!("任务 [{}] 合并已取消，已删除不完整的输出文件", id);
+            cancel_merge(app_handle, id)?;
+            return Ok(false);
+        }
+    };
+    if let Err(e) = result {
+        fail_merge(app_handle, id).await?;
+        return Err(e);
+    }
+    Ok(true)
+}
+
+/// 兜底拼接的输出路径：TS 分片输出 `.ts`，fMP4 分片输出 `.mp4`
+fn fallback_output_path(output_dir: &str, name: &str, options: &MergeOptions) -> PathBuf {
+    let extension = if options.init_segment { "mp4" } else { "ts" };
+    resolve_output_path(output_dir, name, extension, options.overwrite)
+}
+
+/// 按字节拼接到兜底输出路径，用于 ffmpeg 不可用、无法运行或合并失败时
+///
+/// 单独的音频轨道只能由 ffmpeg 封装，兜底输出中没有音频，发出警告提示用户。
+async fn fallback_merge(
+    app_handle: &AppHandle,
+    id: &str,
+    files: &[String],
+    output_path: &std::path::Path,
+    options: &MergeOptions,
+    control: &DownloadControl,
+    ffmpeg: Option<&str>,
+) -> Result<()> {
+    if options.audio.is_some() {
+        let message = "音频在单独的轨道中，需要 ffmpeg 才能封装，直接拼接的输出文件没有声音";
+        log::warn!("任务 [{}] {}", id, message);
+        emit_task_event(
+            app_handle,
+            "merge_warning",
+            serde_json::json!({
+                "id": id,
+                "message": message,
+            }),
+            TaskUpdate::new(id, TaskPhase::Merging, 4, message),
+        )?;
+    }
+    fallback_concat(app_handle, id, files, output_path, control, ffmpeg, options).await
+}
+
+/// ffmpeg 不可用或合并失败时的兜底：不经过 ffmpeg，直接按字节拼接分片
+///
+/// 本地分片均已解密，TS 分片拼接为 `.ts`，fMP4 分片（初始化分片在前）拼接为分段 MP4，
+/// 都可以直接播放，但未经重新封装，部分播放器可能无法拖动进度。
+/// ffmpeg 仍能运行时（如合并失败）同样校验输出文件。
+async fn fallback_concat(
+    app_handle: &AppHandle,
+    id: &str,
+    files: &[String],
+    output_path: &std::path::Path,
+    control: &DownloadControl,
+    ffmpeg: Option<&str>,
+    options: &MergeOptions,
+) -> Result<()> {
+    log::warn!(
+        "任务 [{}] 改为按字节拼接 {} 个文件 → {}",
+        id,
+        files.len(),
+        output_path.display()
+    );
+    let message = format!(
+        "ffmpeg 不可用或合并失败，已直接拼接分片输出为 {}，如无法拖动进度请用其他工具重新封装",
+        output_path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+    );
+    let mut update = TaskUpdate::new(id, TaskPhase::Merging, 4, message.clone());
+    update.details = Some(serde_json::json!({ "fallback": true }));
+    emit_task_event(
+        app_handle,
+        "merge_warning",
+        serde_json::json!({
+            "id": id,
+            "message": &message,
+            "fallback": true,
+        }),
+        update,
+    )?;
+    if !concat_to_output(app_handle, id, files, output_path, control).await? {
+        return Ok(());
+    }
+    let output_file_str = output_path.to_string_lossy();
+    if let Some(ffmpeg) = ffmpeg {
+        verify_output(
+            app_handle,
+            id,
+            ffmpeg,
+            &output_file_str,
+            options.total_duration,
+        )
+        .await;
+    }
+    // ffmpeg 不可用或刚刚合并失败，不再用它生成封面
+    complete_merge(
+        app_handle,
+        id,
+        &output_file_str,
+        None,
+        options.kept_temp_dir.as_deref(),
+    )
+    .await
+}
+
+/// 合并成功，通知前端输出文件路径
+///
+/// 传入 `thumbnail_ffmpeg` 时先生成封面，封面事件在合并成功事件之前发出。
+/// 保留了临时目录时，事件中带上目录路径（`tempDir`）。
+async fn complete_merge(
+    app_handle: &AppHandle,
+    id: &str,
+    output_file_str: &str,
+    thumbnail_ffmpeg: Option<&str>,
+    kept_temp_dir: Option<&str>,
+) -> Result<()> {
+    if let Some(ffmpeg) = thumbnail_ffmpeg {
+        generate_thumbnail(app_handle, id, ffmpeg, output_file_str).await;
+    }
+
+    let mut update = TaskUpdate::new(id, TaskPhase::Done, 5, "合并成功");
+    update.progress = Some(100);
+    update.details = Some(serde_json::json!({
+        "file": output_file_str,
+        "tempDir": kept_temp_dir,
+    }));
+    emit_task_event(
+        app_handle,
         "merge_video",
         serde_json::json!({
             "id": id,
@@ -238,9 +1519,491 @@ pub async fn merge_files(
             "status": 5,
             "message": "合并成功",
             "file": output_file_str,
+            "tempDir": kept_temp_dir,
         }),
+        update,
     )?;
 
     log::info!("{} 合并完成 → {}", id, output_file_str);
+    let file_name = std::path::Path::new(output_file_str)
+        .file_name()
+        .map(|n| n.to_string_lossy())
+        .unwrap_or_default();
+    notify_task_completed(app_handle, &file_name);
     Ok(())
 }
+
+/// 封面截取位置占总时长的比例
+const THUMBNAIL_POSITION: f64 = 0.1;
+/// 截取位置距离结尾至少保留的时长（秒），避免很短的视频定位到结尾之后截不到画面
+const THUMBNAIL_END_MARGIN: f64 = 0.5;
+
+/// 生成封面并通知前端；失败只记录日志，不影响合并结果
+async fn generate_thumbnail(app_handle: &AppHandle, id: &str, ffmpeg: &str, video: &str) {
+    match extract_thumbnail(ffmpeg, video).await {
+        Ok(thumbnail) => {
+            let thumbnail = thumbnail.to_string_lossy();
+            log::info!("任务 [{}] 已生成封面 → {}", id, thumbnail);
+            let mut update = TaskUpdate::new(id, TaskPhase::Merging, 4, "已生成封面");
+            update.details = Some(serde_json::json!({ "thumbnail": &thumbnail }));
+            emit_task_event(
+                app_handle,
+                "thumbnail_generated",
+                serde_json::json!({
+                    "id": id,
+                    "thumbnail": &thumbnail,
+                }),
+                update,
+            )
+            .ok();
+        }
+        Err(e) => log::warn!("任务 [{}] 生成封面失败: {}", id, e),
+    }
+}
+
+/// 用 ffmpeg 从视频约 10% 处截取一帧，保存为同名 `.jpg`
+///
+/// 时长未知时截取第一帧；定位后没有得到画面（如时间戳不从 0 开始）时再从开头截取一次。
+async fn extract_thumbnail(ffmpeg: &str, video: &str) -> Result<PathBuf> {
+    let thumbnail = std::path::Path::new(video).with_extension("jpg");
+    let thumbnail_str = thumbnail.to_string_lossy();
+
+    let duration = probe_local_media(ffmpeg, video).await?.segment_duration;
+    let seek = duration.map_or(0.0, |d| {
+        (d * THUMBNAIL_POSITION)
+            .min(d - THUMBNAIL_END_MARGIN)
+            .max(0.0)
+    });
+
+    let mut seeks = vec![seek];
+    if seek > 0.0 {
+        seeks.push(0.0);
+    }
+    for seek in seeks {
+        let seek = format!("{:.3}", seek);
+        let status = create_ffmpeg_command(ffmpeg)
+            .args(["-hide_banner", "-loglevel", "error", "-y", "-ss", &seek])
+            .args(["-i", video, "-frames:v", "1", "-q:v", "2", &thumbnail_str])
+            .status()
+            .await?;
+        let written = tokio::fs::metadata(&thumbnail)
+            .await
+            .is_ok_and(|m| m.len() > 0);
+        if status.success() && written {
+            return Ok(thumbnail);
+        }
+        log::debug!("从 {} 秒处截取封面失败: {}", seek, status);
+    }
+    Err(anyhow::anyhow!("ffmpeg 未能截取到画面"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inputs<'a>(
+        audio_list: Option<(&'a str, bool)>,
+        network_args: &'a [String],
+    ) -> MuxInputs<'a> {
+        MuxInputs {
+            video_list: ("concat.txt", true),
+            audio_list,
+            timestamp_mode: TimestampMode::Off,
+            network_args,
+            codec_args: &["-c", "copy"],
+            output_format: OutputFormat::Mp4,
+            output: "out.mp4",
+        }
+    }
+
+    #[test]
+    fn auto_timestamp_mode_regenerates_for_long_streams() {
+        let auto = TimestampMode::Auto;
+        assert_eq!(auto.resolve(None, false), TimestampMode::Off);
+        assert_eq!(auto.resolve(Some(600.0), false), TimestampMode::Off);
+        // 阈值本身不算长流
+        assert_eq!(
+            auto.resolve(Some(LONG_STREAM_SECONDS), false),
+            TimestampMode::Off
+        );
+        assert_eq!(
+            auto.resolve(Some(LONG_STREAM_SECONDS + 1.0), false),
+            TimestampMode::GenPts
+        );
+    }
+
+    #[test]
+    fn auto_timestamp_mode_regenerates_across_discontinuities() {
+        let auto = TimestampMode::Auto;
+        assert_eq!(auto.resolve(None, true), TimestampMode::GenPts);
+        assert_eq!(auto.resolve(Some(60.0), true), TimestampMode::GenPts);
+    }
+
+    #[test]
+    fn explicit_timestamp_mode_is_kept() {
+        let long = Some(LONG_STREAM_SECONDS * 2.0);
+        for mode in [
+            TimestampMode::Off,
+            TimestampMode::GenPts,
+            TimestampMode::CopyTs,
+        ] {
+            assert_eq!(mode.resolve(long, true), mode);
+            assert_eq!(mode.resolve(None, false), mode);
+        }
+        assert_eq!(TimestampMode::Off.input_args(), [] as [&str; 0]);
+        assert_eq!(TimestampMode::GenPts.input_args(), ["-fflags", "+genpts"]);
+        assert_eq!(TimestampMode::CopyTs.input_args(), ["-copyts"]);
+    }
+
+    #[test]
+    fn duration_is_estimated_from_sample_segment() {
+        // 播放列表给出总时长时直接使用
+        assert_eq!(estimate_duration(Some(7300.0), Some(4.0), 10), Some(7300.0));
+        // 没有总时长时按样本分片时长 × 分片数估算，超过阈值时 Auto 重新生成时间戳
+        let estimated = estimate_duration(None, Some(6.0), 1300);
+        assert_eq!(estimated, Some(7800.0));
+        assert_eq!(
+            TimestampMode::Auto.resolve(estimated, false),
+            TimestampMode::GenPts
+        );
+        assert_eq!(estimate_duration(None, None, 1300), None);
+    }
+
+    /// 合并一段时间戳中途跳变（模拟插播广告）的分片，比较关闭和开启 `+genpts` 时输出的时长：
+    /// `cargo test timestamp_modes_keep_duration -- --ignored --nocapture`
+    ///
+    /// 需要 PATH 中有 ffmpeg。
+    #[tokio::test]
+    #[ignore = "需要本机安装 ffmpeg"]
+    async fn timestamp_modes_keep_duration() {
+        const SEGMENT_SECS: f64 = 2.0;
+        let ffmpeg = find_ffmpeg_in_path().expect("PATH 中未找到 ffmpeg");
+        let ffmpeg = ffmpeg.to_string_lossy().into_owned();
+        let dir = std::env::temp_dir().join(format!("m3u8_genpts_test_{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let temp_dir = dir.to_string_lossy().into_owned();
+
+        // 第二个分片的时间戳从 100 秒开始，第三个又回到 4 秒
+        let mut files = Vec::new();
+        for (index, offset) in [0, 100, 4].into_iter().enumerate() {
+            let file = format!("{}/part_{}.ts", temp_dir, index);
+            let duration = format!("duration={}", SEGMENT_SECS);
+            let status = create_ffmpeg_command(&ffmpeg)
+                .args(["-y", "-hide_banner", "-loglevel", "error"])
+                .args(["-f", "lavfi", "-i"])
+                .arg(format!("testsrc=size=320x240:rate=25:{}", duration))
+                .args(["-f", "lavfi", "-i"])
+                .arg(format!("sine=frequency=440:{}", duration))
+                .args(["-c:v", "mpeg4", "-c:a", "aac", "-output_ts_offset"])
+                .arg(offset.to_string())
+                .args(["-f", "mpegts", &file])
+                .status()
+                .await
+                .unwrap();
+            assert!(status.success(), "生成测试分片失败");
+            files.push(file);
+        }
+        let list_path = format!("{}/concat.txt", temp_dir);
+        write_concat_list("test", &list_path, &files, &temp_dir, true)
+            .await
+            .unwrap();
+
+        let mut durations = Vec::new();
+        for mode in [TimestampMode::Off, TimestampMode::GenPts] {
+            let output = format!("{}/out_{:?}.mp4", temp_dir, mode);
+            let mut mux = inputs(None, &[]);
+            mux.video_list = (&list_path, true);
+            mux.timestamp_mode = mode;
+            mux.output = &output;
+            let status = create_ffmpeg_command(&ffmpeg)
+                .args(["-hide_banner", "-loglevel", "error"])
+                .args(mux_args(&mux))
+                .status()
+                .await
+                .unwrap();
+            let duration = probe_local_media(&ffmpeg, &output)
+                .await
+                .ok()
+                .and_then(|probe| probe.segment_duration);
+            let merged = status.success();
+            println!("{:?}: 合并成功 {}, 时长 {:?}", mode, merged, duration);
+            durations.push(duration);
+        }
+        tokio::fs::remove_dir_all(&dir).await.ok();
+
+        let expected = SEGMENT_SECS * files.len() as f64;
+        let error = |d: Option<f64>| d.map_or(f64::INFINITY, |d| (d - expected).abs());
+        // 重新生成时间戳后时长与分片时长之和一致，且不比不处理时偏差更大
+        let (off, genpts) = (error(durations[0]), error(durations[1]));
+        assert!(genpts < 0.5, "{:?}", durations);
+        assert!(genpts <= off + 0.1, "{:?}", durations);
+    }
+
+    #[test]
+    fn mux_args_without_audio_track() {
+        let args = mux_args(&inputs(None, &[]));
+        assert_eq!(
+            args,
+            [
+                "-y",
+                "-f",
+                "concat",
+                "-i",
+                "concat.txt",
+                "-c",
+                "copy",
+                "-map",
+                "0",
+                "-avoid_negative_ts",
+                "make_zero",
+                "-bsf:a",
+                "aac_adtstoasc",
+                "out.mp4",
+            ]
+        );
+    }
+
+    #[test]
+    fn mux_args_map_separate_audio_track() {
+        let mut mux = inputs(Some(("audio/concat.txt", false)), &[]);
+        mux.timestamp_mode = TimestampMode::GenPts;
+        let args = mux_args(&mux);
+        assert_eq!(
+            args,
+            [
+                "-y",
+                "-f",
+                "concat",
+                "-fflags",
+                "+genpts",
+                "-i",
+                "concat.txt",
+                "-f",
+                "concat",
+                "-safe",
+                "0",
+                "-fflags",
+                "+genpts",
+                "-i",
+                "audio/concat.txt",
+                "-c",
+                "copy",
+                "-map",
+                "0",
+                "-map",
+                "1:a",
+                "-avoid_negative_ts",
+                "make_zero",
+                "-bsf:a",
+                "aac_adtstoasc",
+                "out.mp4",
+            ]
+        );
+    }
+
+    #[test]
+    fn mux_args_network_args_precede_first_input() {
+        let network = vec!["-protocol_whitelist".to_string(), "file,http".to_string()];
+        let mut mux = inputs(None, &network);
+        mux.video_list = ("/tmp/concat.txt", false);
+        mux.output_format = OutputFormat::Ts;
+        let args = mux_args(&mux);
+        assert_eq!(
+            &args[..9],
+            [
+                "-y",
+                "-f",
+                "concat",
+                "-safe",
+                "0",
+                "-protocol_whitelist",
+                "file,http",
+                "-i",
+                "/tmp/concat.txt",
+            ]
+        );
+        // TS 输出保留 ADTS 封装
+        assert!(!args.contains(&"aac_adtstoasc"));
+    }
+
+    /// 按字节拼接逐个打开、关闭分片：分片数远超常见的文件描述符上限（1024）时也能完成
+    #[tokio::test]
+    async fn concat_segment_group_merges_10k_files() {
+        const FILES: usize = 10_000;
+        let dir = std::env::temp_dir().join(format!("m3u8_concat_test_{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let mut inputs = Vec::with_capacity(FILES);
+        for index in 0..FILES {
+            let path = dir.join(format!("part_{}.ts", index));
+            tokio::fs::write(&path, (index as u16).to_le_bytes())
+                .await
+                .unwrap();
+            inputs.push(path.to_string_lossy().into_owned());
+        }
+        let output = dir.join("output.ts");
+
+        let result = concat_segment_group(&inputs, &output.to_string_lossy()).await;
+        let merged = tokio::fs::read(&output).await;
+        tokio::fs::remove_dir_all(&dir).await.ok();
+
+        result.unwrap();
+        let merged = merged.unwrap();
+        assert_eq!(merged.len(), FILES * 2);
+        for (index, pair) in merged.chunks(2).enumerate() {
+            assert_eq!(pair, (index as u16).to_le_bytes());
+        }
+    }
+
+    #[test]
+    fn concat_line_escapes_single_quotes() {
+        assert_eq!(
+            concat_file_line("/tmp/a/part_0.ts"),
+            "file '/tmp/a/part_0.ts'\n"
+        );
+        assert_eq!(
+            concat_file_line("/home/o'brien/it's/part_0.ts"),
+            "file '/home/o'\\''brien/it'\\''s/part_0.ts'\n"
+        );
+    }
+
+    /// 临时目录路径含单引号时，列表中每一行仍是 ffmpeg 可以解析的完整路径
+    #[tokio::test]
+    async fn concat_list_in_dir_with_apostrophe() {
+        let dir = std::env::temp_dir().join(format!("m3u8_it's_test_{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let temp_dir = dir.to_string_lossy().into_owned();
+        let files: Vec<String> = (0..2)
+            .map(|i| format!("{}/part_{}.ts", temp_dir, i))
+            .collect();
+        let list_path = format!("{}/concat.txt", temp_dir);
+
+        let safe_mode = write_concat_list("test", &list_path, &files, &temp_dir, false).await;
+        let list = tokio::fs::read_to_string(&list_path).await;
+        tokio::fs::remove_dir_all(&dir).await.ok();
+
+        assert!(!safe_mode.unwrap());
+        let escaped = temp_dir.replace('\'', r"'\''");
+        assert_eq!(
+            list.unwrap(),
+            format!(
+                "ffconcat version 1.0\nfile '{0}/part_0.ts'\nfile '{0}/part_1.ts'\n",
+                escaped
+            )
+        );
+    }
+
+    /// 安全模式只写入相对文件名，临时目录路径中的特殊字符不影响列表
+    #[tokio::test]
+    async fn concat_list_safe_mode_with_unusual_dir() {
+        let dir = std::env::temp_dir().join(format!("m3u8 [safe] ü's_{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let temp_dir = dir.to_string_lossy().into_owned();
+        let list_path = format!("{}/concat.txt", temp_dir);
+        let files: Vec<String> = (0..2)
+            .map(|i| format!("{}/part_{}.ts", temp_dir, i))
+            .collect();
+
+        let safe = write_concat_list("test", &list_path, &files, &temp_dir, true).await;
+        let safe_list = tokio::fs::read_to_string(&list_path).await;
+        let unsafe_mode = write_concat_list("test", &list_path, &files, &temp_dir, false).await;
+        let unsafe_list = tokio::fs::read_to_string(&list_path).await;
+        tokio::fs::remove_dir_all(&dir).await.ok();
+
+        assert!(safe.unwrap());
+        assert_eq!(
+            safe_list.unwrap(),
+            "ffconcat version 1.0\nfile 'part_0.ts'\nfile 'part_1.ts'\n"
+        );
+        assert!(!unsafe_mode.unwrap());
+        let escaped = temp_dir.replace('\'', r"'\''");
+        assert!(unsafe_list
+            .unwrap()
+            .contains(&format!("file '{}/part_1.ts'\n", escaped)));
+    }
+
+    #[test]
+    fn unsafe_names_fall_back_to_absolute_paths() {
+        let entries = |files: &[&str]| {
+            let files: Vec<String> = files.iter().map(|f| f.to_string()).collect();
+            relative_concat_entries(&files, "/tmp/task")
+        };
+        assert_eq!(
+            entries(&["/tmp/task/part_0.ts", "/tmp/task/part_1.ts"]),
+            Some(vec!["part_0.ts".to_string(), "part_1.ts".to_string()])
+        );
+        assert_eq!(entries(&["/tmp/task/part_0.ts", "/tmp/task/a b.ts"]), None);
+        assert_eq!(entries(&["/tmp/task/part_0.ts", "/other/part_1.ts"]), None);
+        assert!(!is_concat_safe_name(".hidden.ts"));
+        assert!(is_concat_safe_name("group_0-1.ts"));
+    }
+
+    #[test]
+    fn network_args_only_for_remote_inputs() {
+        let network = FfmpegNetwork {
+            proxy: Some("http://127.0.0.1:8080".to_string()),
+            headers: vec![
+                ("Referer".to_string(), "https://example.com/".to_string()),
+                ("User-Agent".to_string(), "test".to_string()),
+            ],
+        };
+        // 本地分片合并时不追加任何参数
+        assert!(network
+            .input_args(&["/tmp/task/part_0.ts", "/tmp/task/part_1.ts"])
+            .is_empty());
+
+        let args = network.input_args(&["https://cdn.example.com/init.mp4", "/tmp/part_0.m4s"]);
+        assert_eq!(
+            args,
+            [
+                "-protocol_whitelist",
+                "file,http,https,tcp,tls,crypto",
+                "-headers",
+                "Referer: https://example.com/\r\nUser-Agent: test\r\n",
+                "-http_proxy",
+                "http://127.0.0.1:8080",
+            ]
+        );
+    }
+
+    #[test]
+    fn socks_proxy_is_not_passed_to_ffmpeg() {
+        let network = FfmpegNetwork {
+            proxy: Some("socks5://127.0.0.1:1080".to_string()),
+            headers: Vec::new(),
+        };
+        assert_eq!(
+            network.input_args(&["HTTP://cdn.example.com/init.mp4"]),
+            ["-protocol_whitelist", "file,http,https,tcp,tls,crypto"]
+        );
+    }
+
+    /// 合并中途取消：长时间运行的进程被立即终止，不完整的输出文件被删除
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn cancel_during_merge_kills_process() {
+        let dir = std::env::temp_dir().join(format!("m3u8_cancel_test_{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let output_path = dir.join("output.mp4");
+        tokio::fs::write(&output_path, b"partial").await.unwrap();
+        // 用长时间运行的进程代替正在重新编码的 ffmpeg
+        let mut child = process::Command::new("sleep").arg("30").spawn().unwrap();
+        let control = Arc::new(DownloadControl::new());
+        let canceller = Arc::clone(&control);
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            canceller.cancel();
+        });
+
+        let start = std::time::Instant::now();
+        let result = wait_ffmpeg("test", &mut child, &control, &output_path).await;
+        let elapsed = start.elapsed();
+        let exited = child.try_wait().unwrap().is_some();
+        let output_exists = output_path.exists();
+        tokio::fs::remove_dir_all(&dir).await.ok();
+
+        assert!(result.unwrap().is_none());
+        assert!(elapsed < std::time::Duration::from_secs(5), "{:?}", elapsed);
+        assert!(exited);
+        assert!(!output_exists);
+    }
+}
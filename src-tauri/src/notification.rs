@@ -0,0 +1,42 @@
+//! 桌面通知模块
+//! 窗口默认最小化到托盘，下载完成或失败时发送系统通知提醒用户。
+//! 可通过 settings.dat 中的 `notificationsEnabled` 关闭（默认开启）。
+
+use tauri::AppHandle;
+use tauri_plugin_notification::NotificationExt;
+use tauri_plugin_store::StoreExt;
+
+/// 是否发送桌面通知
+fn notifications_enabled(app_handle: &AppHandle) -> bool {
+    app_handle
+        .store("settings.dat")
+        .ok()
+        .and_then(|s| s.get("notificationsEnabled"))
+        .and_then(|v| v.as_bool())
+        .unwrap_or(true)
+}
+
+fn show(app_handle: &AppHandle, title: &str, body: &str) {
+    if !notifications_enabled(app_handle) {
+        return;
+    }
+    if let Err(e) = app_handle
+        .notification()
+        .builder()
+        .title(title)
+        .body(body)
+        .show()
+    {
+        log::warn!("发送桌面通知失败: {}", e);
+    }
+}
+
+/// 合并成功，通知中显示输出文件名
+pub fn notify_task_completed(app_handle: &AppHandle, file_name: &str) {
+    show(app_handle, "下载完成", file_name);
+}
+
+/// 下载或合并失败
+pub fn notify_task_failed(app_handle: &AppHandle, name: &str, error: &str) {
+    show(app_handle, "下载失败", &format!("{}: {}", name, error));
+}
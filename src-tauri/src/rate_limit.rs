@@ -0,0 +1,57 @@
+//! 下载限速模块
+//! 令牌桶限速器，由同一任务的所有工作协程共享，限制的是整个任务的总速度。
+
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// 桶容量对应的时长，允许短时间内的突发
+const BURST_WINDOW: Duration = Duration::from_secs(1);
+
+struct Bucket {
+    /// 当前可用的字节数，可以为负（表示已透支，需要等待补足）
+    available: f64,
+    last_refill: Instant,
+}
+
+/// 令牌桶限速器
+pub struct RateLimiter {
+    bytes_per_sec: f64,
+    bucket: Mutex<Bucket>,
+}
+
+impl RateLimiter {
+    pub fn new(bytes_per_sec: u64) -> Self {
+        let bytes_per_sec = bytes_per_sec.max(1) as f64;
+        Self {
+            bytes_per_sec,
+            bucket: Mutex::new(Bucket {
+                available: bytes_per_sec * BURST_WINDOW.as_secs_f64(),
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    /// 消耗 `bytes` 个字节的额度，额度不足时等待
+    ///
+    /// 先记账再等待：锁只在计算时持有，多个协程可以同时等待各自透支的部分，
+    /// 总体速度仍不超过限制。
+    pub async fn acquire(&self, bytes: usize) {
+        let wait = {
+            let mut bucket = self.bucket.lock().await;
+            let now = Instant::now();
+            let capacity = self.bytes_per_sec * BURST_WINDOW.as_secs_f64();
+            let refill = now.duration_since(bucket.last_refill).as_secs_f64() * self.bytes_per_sec;
+            bucket.available = (bucket.available + refill).min(capacity);
+            bucket.last_refill = now;
+            bucket.available -= bytes as f64;
+            if bucket.available < 0.0 {
+                Duration::from_secs_f64(-bucket.available / self.bytes_per_sec)
+            } else {
+                Duration::ZERO
+            }
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
@@ -0,0 +1,527 @@
+//! SAMPLE-AES 解密模块
+//! 按 HLS 的 MPEG-2 流加密格式解密 TS 分片：只有 H.264 的部分 NAL 单元和 AAC 帧的部分数据被加密，
+//! 解密后按原有的包位置重新封装，PAT/PMT 等其余数据原样保留。
+
+use aes::Aes128;
+use anyhow::Result;
+use cbc::Decryptor;
+use cipher::{BlockDecryptMut, KeyIvInit};
+use std::collections::HashMap;
+
+const TS_PACKET_SIZE: usize = 188;
+const TS_SYNC_BYTE: u8 = 0x47;
+const BLOCK_SIZE: usize = 16;
+
+/// PMT 中 SAMPLE-AES 加密流的 stream_type
+const STREAM_TYPE_H264: u8 = 0xdb;
+const STREAM_TYPE_AAC: u8 = 0xcf;
+const STREAM_TYPE_AC3: u8 = 0xc1;
+const STREAM_TYPE_EAC3: u8 = 0xc2;
+
+/// H.264 NAL 单元开头不加密的字节数
+const NAL_CLEAR_LEADER: usize = 32;
+/// 每个加密块之后不加密的字节数（1:9 的加密模式）
+const NAL_CLEAR_STRIDE: usize = 144;
+/// 音频帧（去掉 ADTS 头后）开头不加密的字节数
+const AUDIO_CLEAR_LEADER: usize = 16;
+
+/// 分片无法按 SAMPLE-AES 解密（不支持的编码、不是 TS 数据等）
+///
+/// 同样的数据重新下载也无法解密，下载时遇到该错误不再重试。
+#[derive(Debug)]
+pub struct SampleAesError(String);
+
+impl std::fmt::Display for SampleAesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for SampleAesError {}
+
+fn sample_aes_error(message: impl Into<String>) -> anyhow::Error {
+    SampleAesError(message.into()).into()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Codec {
+    H264,
+    Aac,
+}
+
+/// 正在拼接的一个加密 PES 包
+struct PendingPes {
+    codec: Codec,
+    /// 该 PES 占用的 TS 包在输出中的位置（只含带负载的包）
+    slots: Vec<usize>,
+    data: Vec<u8>,
+}
+
+/// 解密一个 SAMPLE-AES 加密的 TS 分片
+pub fn decrypt_ts_segment(data: &[u8], key: &[u8], iv: &[u8]) -> Result<Vec<u8>> {
+    if key.len() != BLOCK_SIZE || iv.len() != BLOCK_SIZE {
+        return Err(sample_aes_error(format!(
+            "密钥或 IV 长度无效（密钥 {} 字节，IV {} 字节，应为 {} 字节）",
+            key.len(),
+            iv.len(),
+            BLOCK_SIZE
+        )));
+    }
+    if data.len() % TS_PACKET_SIZE != 0 || data.first() != Some(&TS_SYNC_BYTE) {
+        return Err(sample_aes_error(
+            "SAMPLE-AES 分片不是有效的 TS 数据，分片可能不完整",
+        ));
+    }
+
+    let mut packets: Vec<[u8; TS_PACKET_SIZE]> = Vec::with_capacity(data.len() / TS_PACKET_SIZE);
+    let mut removed = Vec::with_capacity(packets.capacity());
+    let mut pmt_pids = Vec::new();
+    let mut encrypted_pids: HashMap<u16, Codec> = HashMap::new();
+    let mut pending: HashMap<u16, PendingPes> = HashMap::new();
+
+    for chunk in data.chunks_exact(TS_PACKET_SIZE) {
+        if chunk[0] != TS_SYNC_BYTE {
+            return Err(sample_aes_error("TS 包同步字节错误，分片可能已损坏"));
+        }
+        let packet: [u8; TS_PACKET_SIZE] = chunk.try_into()?;
+        let slot = packets.len();
+        packets.push(packet);
+        removed.push(false);
+
+        let pid = packet_pid(&packet);
+        let unit_start = packet[1] & 0x40 != 0;
+        let Some(payload) = packet_payload(&packet) else {
+            continue;
+        };
+
+        if pid == 0 && unit_start {
+            pmt_pids = parse_pat(payload);
+        } else if pmt_pids.contains(&pid) && unit_start {
+            for (stream_pid, codec) in parse_pmt(payload)? {
+                encrypted_pids.insert(stream_pid, codec);
+            }
+        } else if let Some(&codec) = encrypted_pids.get(&pid) {
+            if unit_start {
+                if let Some(pes) = pending.remove(&pid) {
+                    flush_pes(&mut packets, &mut removed, pes, key, iv)?;
+                }
+                pending.insert(
+                    pid,
+                    PendingPes {
+                        codec,
+                        slots: Vec::new(),
+                        data: Vec::new(),
+                    },
+                );
+            }
+            // 分片开头可能是上一个 PES 的后续部分，缺少 PES 头无法解密，原样保留
+            if let Some(pes) = pending.get_mut(&pid) {
+                pes.slots.push(slot);
+                pes.data.extend_from_slice(payload);
+            }
+        }
+    }
+    for (_, pes) in pending {
+        flush_pes(&mut packets, &mut removed, pes, key, iv)?;
+    }
+
+    if encrypted_pids.is_empty() {
+        log::warn!("SAMPLE-AES 分片中未找到加密的音视频流，按原样保存");
+        return Ok(data.to_vec());
+    }
+
+    // 重新封装后包的数量可能减少，重新编号加密流的连续计数器
+    let mut counters: HashMap<u16, u8> = HashMap::new();
+    let mut output = Vec::with_capacity(data.len());
+    for (packet, removed) in packets.iter_mut().zip(removed) {
+        if removed {
+            continue;
+        }
+        let pid = packet_pid(packet);
+        if encrypted_pids.contains_key(&pid) && packet[3] & 0x10 != 0 {
+            let counter = counters.entry(pid).or_insert(packet[3] & 0x0f);
+            packet[3] = (packet[3] & 0xf0) | *counter;
+            *counter = (*counter + 1) & 0x0f;
+        }
+        output.extend_from_slice(packet);
+    }
+    Ok(output)
+}
+
+fn packet_pid(packet: &[u8]) -> u16 {
+    (u16::from(packet[1] & 0x1f) << 8) | u16::from(packet[2])
+}
+
+/// TS 包头（含调整字段）的长度
+fn packet_header_len(packet: &[u8]) -> usize {
+    if packet[3] & 0x20 != 0 {
+        5 + packet[4] as usize
+    } else {
+        4
+    }
+}
+
+fn packet_payload(packet: &[u8]) -> Option<&[u8]> {
+    if packet[3] & 0x10 == 0 {
+        return None;
+    }
+    packet.get(packet_header_len(packet)..)
+}
+
+/// 跳过 pointer_field，返回 PSI 段的内容
+fn psi_section(payload: &[u8]) -> Option<&[u8]> {
+    let pointer = *payload.first()? as usize;
+    let section = payload.get(1 + pointer..)?;
+    let length = (usize::from(section.get(1)? & 0x0f) << 8) | usize::from(*section.get(2)?);
+    section.get(..3 + length)
+}
+
+/// 解析 PAT，返回各节目的 PMT PID
+fn parse_pat(payload: &[u8]) -> Vec<u16> {
+    let Some(section) = psi_section(payload) else {
+        return Vec::new();
+    };
+    // 8 字节段头之后是 4 字节的节目条目，末尾 4 字节为 CRC
+    let entries = section
+        .get(8..section.len().saturating_sub(4))
+        .unwrap_or_default();
+    entries
+        .chunks_exact(4)
+        .filter(|entry| entry[0] != 0 || entry[1] != 0) // 节目号 0 为网络 PID
+        .map(|entry| (u16::from(entry[2] & 0x1f) << 8) | u16::from(entry[3]))
+        .collect()
+}
+
+/// 解析 PMT，返回 SAMPLE-AES 加密的流
+fn parse_pmt(payload: &[u8]) -> Result<Vec<(u16, Codec)>> {
+    let Some(section) = psi_section(payload) else {
+        return Ok(Vec::new());
+    };
+    let Some(program_info_len) = section.get(10..12) else {
+        return Ok(Vec::new());
+    };
+    let program_info_len =
+        (usize::from(program_info_len[0] & 0x0f) << 8) | usize::from(program_info_len[1]);
+    let end = section.len().saturating_sub(4);
+    let mut pos = 12 + program_info_len;
+    let mut streams = Vec::new();
+    while pos + 5 <= end {
+        let stream_type = section[pos];
+        let pid = (u16::from(section[pos + 1] & 0x1f) << 8) | u16::from(section[pos + 2]);
+        let info_len = (usize::from(section[pos + 3] & 0x0f) << 8) | usize::from(section[pos + 4]);
+        match stream_type {
+            STREAM_TYPE_H264 => streams.push((pid, Codec::H264)),
+            STREAM_TYPE_AAC => streams.push((pid, Codec::Aac)),
+            STREAM_TYPE_AC3 | STREAM_TYPE_EAC3 => {
+                return Err(sample_aes_error(
+                    "暂不支持 SAMPLE-AES 加密的 AC-3/E-AC-3 音频",
+                ));
+            }
+            _ => {}
+        }
+        pos += 5 + info_len;
+    }
+    Ok(streams)
+}
+
+/// 解密一个 PES 包并写回它原来占用的 TS 包
+///
+/// H.264 去掉防竞争字节后数据会变短，多出来的 TS 包被移除，最后一个包用调整字段填充。
+fn flush_pes(
+    packets: &mut [[u8; TS_PACKET_SIZE]],
+    removed: &mut [bool],
+    pes: PendingPes,
+    key: &[u8],
+    iv: &[u8],
+) -> Result<()> {
+    let data = decrypt_pes(&pes.data, pes.codec, key, iv)?;
+    let mut offset = 0;
+    for &slot in &pes.slots {
+        let packet = &mut packets[slot];
+        let capacity = TS_PACKET_SIZE - packet_header_len(packet);
+        let take = capacity.min(data.len() - offset);
+        if take == 0 {
+            removed[slot] = true;
+            continue;
+        }
+        if take < capacity {
+            stuff_packet(packet, capacity - take);
+        }
+        let start = packet_header_len(packet);
+        packet[start..].copy_from_slice(&data[offset..offset + take]);
+        offset += take;
+    }
+    if offset < data.len() {
+        return Err(sample_aes_error(
+            "SAMPLE-AES 解密后的数据超出原有 TS 包容量",
+        ));
+    }
+    Ok(())
+}
+
+/// 用调整字段为 TS 包补充 `padding` 个填充字节
+fn stuff_packet(packet: &mut [u8; TS_PACKET_SIZE], padding: usize) {
+    if packet[3] & 0x20 != 0 {
+        let af_len = packet[4] as usize;
+        let mut pos = 5 + af_len;
+        let mut padding = padding;
+        if af_len == 0 {
+            // 原调整字段只有长度字节，新增的第一个字节作为标志位
+            packet[pos] = 0x00;
+            pos += 1;
+            padding -= 1;
+        }
+        packet[pos..pos + padding].fill(0xff);
+        packet[4] = (pos + padding - 5) as u8;
+    } else {
+        packet[3] |= 0x20;
+        packet[4] = (padding - 1) as u8;
+        if padding >= 2 {
+            packet[5] = 0x00;
+            packet[6..4 + padding].fill(0xff);
+        }
+    }
+}
+
+/// 解密 PES 包中的基本流数据，并更新 PES 包长度
+fn decrypt_pes(pes: &[u8], codec: Codec, key: &[u8], iv: &[u8]) -> Result<Vec<u8>> {
+    if pes.len() < 9 || pes[..3] != [0x00, 0x00, 0x01] {
+        return Err(sample_aes_error("无效的 PES 包头"));
+    }
+    let header_len = 9 + pes[8] as usize;
+    let (header, es) = pes.split_at(header_len.min(pes.len()));
+    let es = match codec {
+        Codec::H264 => decrypt_h264(es, key, iv),
+        Codec::Aac => decrypt_adts(es, key, iv),
+    };
+
+    let mut output = header.to_vec();
+    // PES 包长度为 0 表示不限长度（常见于视频），无需更新
+    if u16::from_be_bytes([header[4], header[5]]) != 0 {
+        let length = u16::try_from(header.len() - 6 + es.len()).unwrap_or(0);
+        output[4..6].copy_from_slice(&length.to_be_bytes());
+    }
+    output.extend_from_slice(&es);
+    Ok(output)
+}
+
+/// 按 CBC 模式解密若干个不连续的 16 字节块，块之间链式衔接
+// cipher 0.4 的接口仍使用 generic-array 0.14
+#[allow(deprecated)]
+fn decrypt_blocks(data: &mut [u8], offsets: impl Iterator<Item = usize>, key: &[u8], iv: &[u8]) {
+    use cipher::generic_array::GenericArray;
+
+    let mut decryptor =
+        Decryptor::<Aes128>::new(GenericArray::from_slice(key), GenericArray::from_slice(iv));
+    for offset in offsets {
+        decryptor.decrypt_block_mut(GenericArray::from_mut_slice(
+            &mut data[offset..offset + BLOCK_SIZE],
+        ));
+    }
+}
+
+/// 在 `data` 中查找下一个起始码 00 00 01，返回其位置
+fn find_start_code(data: &[u8], from: usize) -> Option<usize> {
+    data.get(from..)?
+        .windows(3)
+        .position(|w| w == [0x00, 0x00, 0x01])
+        .map(|pos| from + pos)
+}
+
+/// 解密 H.264 基本流
+///
+/// 只有长度超过 48 字节的 slice（类型 1 和 5）被加密：先去掉防竞争字节，
+/// 跳过开头 32 字节后每 160 字节中的第一个 16 字节块被加密。
+fn decrypt_h264(es: &[u8], key: &[u8], iv: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(es.len());
+    let Some(mut start_code) = find_start_code(es, 0) else {
+        return es.to_vec();
+    };
+    output.extend_from_slice(&es[..start_code]);
+    loop {
+        let nal_start = start_code + 3;
+        let next = find_start_code(es, nal_start);
+        // 4 字节起始码的前导 0 属于下一个起始码
+        let nal_end = match next {
+            Some(pos) if pos > nal_start && es[pos - 1] == 0 => pos - 1,
+            Some(pos) => pos,
+            None => es.len(),
+        };
+        output.extend_from_slice(&es[start_code..nal_start]);
+
+        let nal = &es[nal_start..nal_end];
+        let nal_type = nal.first().map(|b| b & 0x1f);
+        if matches!(nal_type, Some(1) | Some(5)) && nal.len() > 48 {
+            let mut nal = remove_emulation_prevention(nal);
+            let mut offsets = Vec::new();
+            let mut pos = NAL_CLEAR_LEADER;
+            while nal.len().saturating_sub(pos) > BLOCK_SIZE {
+                offsets.push(pos);
+                pos += BLOCK_SIZE + NAL_CLEAR_STRIDE;
+            }
+            decrypt_blocks(&mut nal, offsets.into_iter(), key, iv);
+            output.extend_from_slice(&nal);
+        } else {
+            output.extend_from_slice(nal);
+        }
+
+        match next {
+            Some(pos) => {
+                output.extend_from_slice(&es[nal_end..pos]);
+                start_code = pos;
+            }
+            None => break,
+        }
+    }
+    output
+}
+
+/// 去掉 NAL 单元中的防竞争字节（00 00 03 中的 03）
+fn remove_emulation_prevention(nal: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(nal.len());
+    let mut zeros = 0;
+    for &byte in nal {
+        if zeros >= 2 && byte == 0x03 {
+            zeros = 0;
+            continue;
+        }
+        zeros = if byte == 0 { zeros + 1 } else { 0 };
+        output.push(byte);
+    }
+    output
+}
+
+/// 解密 ADTS 封装的 AAC 基本流
+///
+/// 每一帧跳过 ADTS 头和随后的 16 字节，其余完整的 16 字节块被加密，每帧重新使用 IV。
+fn decrypt_adts(es: &[u8], key: &[u8], iv: &[u8]) -> Vec<u8> {
+    let mut output = es.to_vec();
+    let mut pos = 0;
+    while pos + 7 <= output.len() {
+        let header = &output[pos..pos + 7];
+        if header[0] != 0xff || header[1] & 0xf0 != 0xf0 {
+            log::warn!("ADTS 同步字错误，停止解密该 PES 中剩余的音频帧");
+            break;
+        }
+        let header_len = if header[1] & 0x01 != 0 { 7 } else { 9 };
+        let frame_len = (usize::from(header[3] & 0x03) << 11)
+            | (usize::from(header[4]) << 3)
+            | usize::from(header[5] >> 5);
+        if frame_len < header_len || pos + frame_len > output.len() {
+            break;
+        }
+        let frame = &mut output[pos + header_len..pos + frame_len];
+        let encrypted = frame.len().saturating_sub(AUDIO_CLEAR_LEADER) / BLOCK_SIZE;
+        let offsets = (0..encrypted).map(|n| AUDIO_CLEAR_LEADER + n * BLOCK_SIZE);
+        decrypt_blocks(frame, offsets, key, iv);
+        pos += frame_len;
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; 16] = [
+        0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e,
+        0x0f,
+    ];
+    const IV: [u8; 16] = [
+        0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d, 0x1e,
+        0x1f,
+    ];
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    /// 把 188 字节以内的数据填充为一个 TS 包
+    fn ts_packet(header: [u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut packet = header.to_vec();
+        packet.extend_from_slice(payload);
+        packet.resize(TS_PACKET_SIZE, 0xff);
+        packet
+    }
+
+    /// PAT 指向 PID 0x100 的 PMT，PMT 中只有一个 `stream_type` 的流（PID 0x101）
+    fn pat_pmt(stream_type: u8) -> Vec<u8> {
+        let pat = hex("0000b00d0001c100000001e10000000000");
+        let pmt = hex(&format!(
+            "0002b0120001c10000e101f000{:02x}e101f00000000000",
+            stream_type
+        ));
+        let mut data = ts_packet([0x47, 0x40, 0x00, 0x10], &pat);
+        data.extend(ts_packet([0x47, 0x41, 0x00, 0x10], &pmt));
+        data
+    }
+
+    #[test]
+    fn decrypts_adts_frame_known_answer() {
+        // 7 字节 ADTS 头 + 53 字节帧数据：16 字节明文、2 个加密块、5 字节尾部明文
+        let header = [0xff, 0xf1, 0x50, 0x80, 0x07, 0x9f, 0xfc];
+        let body: Vec<u8> = (0..53).collect();
+        let mut plain = header.to_vec();
+        plain.extend_from_slice(&body);
+
+        let mut encrypted = plain.clone();
+        let ciphertext = hex("c6a13b37878f5b826f4f8162a1c8d87907853f194efa172561c574ef644f8663");
+        encrypted[7 + 16..7 + 48].copy_from_slice(&ciphertext);
+
+        // 每一帧重新使用 IV，两帧解密结果相同
+        let mut stream = encrypted.clone();
+        stream.extend_from_slice(&encrypted);
+        let mut expected = plain.clone();
+        expected.extend_from_slice(&plain);
+        assert_eq!(decrypt_adts(&stream, &KEY, &IV), expected);
+    }
+
+    #[test]
+    fn decrypts_h264_slice_known_answer() {
+        // IDR slice 共 218 字节：偏移 32 和 192 处各有一个加密块，块之间按 CBC 衔接
+        let mut nal = vec![0x65];
+        nal.extend((1..218).map(|i| (i % 200 + 1) as u8));
+        let ciphertext = hex("07ca2fa13b737ff64010b62d03fe74619ecba3dda28d596c6346138e9755ea1b");
+        let mut encrypted_nal = nal.clone();
+        encrypted_nal[32..48].copy_from_slice(&ciphertext[..16]);
+        encrypted_nal[192..208].copy_from_slice(&ciphertext[16..]);
+
+        // 前面的 SPS 不加密，原样保留
+        let sps = [0x00, 0x00, 0x00, 0x01, 0x67, 0x42, 0x00, 0x1e, 0xab];
+        let build = |slice: &[u8]| {
+            let mut es = sps.to_vec();
+            es.extend_from_slice(&[0x00, 0x00, 0x00, 0x01]);
+            es.extend_from_slice(slice);
+            es
+        };
+        assert_eq!(decrypt_h264(&build(&encrypted_nal), &KEY, &IV), build(&nal));
+    }
+
+    #[test]
+    fn short_h264_slice_is_not_decrypted() {
+        let es = [0x00, 0x00, 0x01, 0x41, 0x9a, 0x02, 0x03, 0x04];
+        assert_eq!(decrypt_h264(&es, &KEY, &IV), es);
+    }
+
+    #[test]
+    fn rejects_non_ts_data_without_retry() {
+        let err = decrypt_ts_segment(b"not a transport stream", &KEY, &IV).unwrap_err();
+        assert!(err.downcast_ref::<SampleAesError>().is_some());
+    }
+
+    #[test]
+    fn rejects_ac3_audio_without_retry() {
+        let err = decrypt_ts_segment(&pat_pmt(STREAM_TYPE_AC3), &KEY, &IV).unwrap_err();
+        assert!(err.downcast_ref::<SampleAesError>().is_some());
+    }
+
+    #[test]
+    fn keeps_segment_without_encrypted_streams() {
+        // 0x1b 为未加密的 H.264，分片按原样返回
+        let data = pat_pmt(0x1b);
+        assert_eq!(decrypt_ts_segment(&data, &KEY, &IV).unwrap(), data);
+    }
+}
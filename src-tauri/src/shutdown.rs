@@ -0,0 +1,72 @@
+//! 退出处理模块
+//! 退出应用前暂停全部下载任务，等待正在下载的分片写入完成并保存任务登记，
+//! 下次启动时可以继续下载。等待时间有上限，不会让退出一直卡住。
+
+use crate::download_manager::DownloadManager;
+use crate::task_registry;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+/// 等待正在下载的分片结束的最长时间
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(3);
+/// 检查分片是否下载结束的间隔
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// 已开始退出流程，避免重复执行
+static EXITING: AtomicBool = AtomicBool::new(false);
+/// 退出前的收尾已完成，之后的退出请求直接放行
+static READY_TO_EXIT: AtomicBool = AtomicBool::new(false);
+
+/// 收尾是否已完成
+pub fn ready_to_exit() -> bool {
+    READY_TO_EXIT.load(Ordering::SeqCst)
+}
+
+/// 完成收尾后退出应用
+///
+/// 代替直接调用 `app.exit(0)`，多次调用只执行一次。
+pub fn exit_gracefully(app_handle: &AppHandle) {
+    if EXITING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    log::info!("Exit the application...");
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        prepare_exit(&app_handle).await;
+        READY_TO_EXIT.store(true, Ordering::SeqCst);
+        app_handle.exit(0);
+    });
+}
+
+/// 暂停全部任务，等待正在下载的分片结束后保存任务登记
+///
+/// 超时未结束的分片保留 `.part` 文件，下次启动时按 Range 续传；
+/// 已写完但未记入清单的分片在续传扫描时会被直接采用。
+async fn prepare_exit(app_handle: &AppHandle) {
+    let manager = app_handle.state::<DownloadManager>();
+    let slots = manager.pause_all().await;
+    if !slots.is_empty() {
+        log::info!(
+            "退出前已暂停 {} 个任务，等待正在下载的分片结束",
+            slots.len()
+        );
+        let idle = async {
+            while slots
+                .iter()
+                .filter_map(|slot| slot.get())
+                .any(|metrics| metrics.in_flight.load(Ordering::Relaxed) > 0)
+            {
+                tokio::time::sleep(SHUTDOWN_POLL_INTERVAL).await;
+            }
+        };
+        if tokio::time::timeout(SHUTDOWN_TIMEOUT, idle).await.is_err() {
+            log::warn!("等待分片下载结束超时，未完成的分片将在下次启动时续传");
+        }
+    }
+
+    if let Err(e) = task_registry::flush(app_handle) {
+        log::warn!("退出前保存任务登记失败: {}", e);
+    }
+    log::logger().flush();
+}
@@ -0,0 +1,115 @@
+//! 任务事件模块
+//! 所有阶段统一通过 `task_update` 事件上报任务状态，前端只需监听一个事件。
+//! 原有的分散事件（download_progress、start_merge_video、merge_video 等）继续保留，
+//! 可通过 settings.dat 中的 `legacyEvents` 关闭（默认开启）。
+
+use serde::Serialize;
+use serde_json::Value;
+use tauri::{AppHandle, Emitter};
+use tauri_plugin_store::StoreExt;
+
+/// 统一事件名
+pub const TASK_UPDATE_EVENT: &str = "task_update";
+
+/// 任务所处阶段
+///
+/// | phase       | status | 说明                                   |
+/// |-------------|--------|----------------------------------------|
+/// | queued      | 1      | 等待中，达到最大并发数时排队             |
+/// | preparing   | 2      | 创建目录、解析播放列表、获取密钥、续传扫描 |
+/// | downloading | 2      | 下载分片中                              |
+/// | paused      | 6      | 已暂停，恢复后继续下载                    |
+/// | downloaded  | 3      | 分片已全部下载，等待合并                  |
+/// | merging     | 4      | 合并中                                  |
+/// | done        | 5      | 合并完成                                |
+/// | cancelled   | 0      | 已取消，可继续下载                        |
+/// | error       | 0/400  | 下载失败为 0（可继续下载），合并失败为 400 |
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskPhase {
+    Queued,
+    Preparing,
+    Downloading,
+    Paused,
+    Downloaded,
+    Merging,
+    Done,
+    Cancelled,
+    Error,
+}
+
+/// `task_update` 事件的负载，各阶段字段含义一致，当前阶段不适用的字段为 null
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskUpdate {
+    pub id: String,
+    pub phase: TaskPhase,
+    /// 与前端一致的状态码，见 [`TaskPhase`]
+    pub status: u32,
+    /// 下载进度百分比（0-100）
+    pub progress: Option<u32>,
+    /// 下载速度，如 "1.20 MB/s"
+    pub speed: Option<String>,
+    /// 预计剩余秒数
+    pub eta: Option<u64>,
+    pub message: String,
+    /// 阶段相关的附加信息（分片数、字节数、输出文件等）
+    pub details: Option<Value>,
+}
+
+impl TaskUpdate {
+    pub fn new(id: &str, phase: TaskPhase, status: u32, message: impl Into<String>) -> Self {
+        Self {
+            id: id.to_string(),
+            phase,
+            status,
+            progress: None,
+            speed: None,
+            eta: None,
+            message: message.into(),
+            details: None,
+        }
+    }
+}
+
+/// 是否继续发送原有的分散事件
+///
+/// 每次读取都会访问设置存储，频繁发送事件的地方（进度监控、合并进度）在开始时读取一次，
+/// 之后通过 [`emit_task_event_with`] 发送。
+pub fn legacy_events_enabled(app_handle: &AppHandle) -> bool {
+    app_handle
+        .store("settings.dat")
+        .ok()
+        .and_then(|s| s.get("legacyEvents"))
+        .and_then(|v| v.as_bool())
+        .unwrap_or(true)
+}
+
+/// 发送 `task_update` 事件，并在兼容开关开启时同时发送原有事件
+pub fn emit_task_event(
+    app_handle: &AppHandle,
+    legacy_event: &str,
+    legacy_payload: Value,
+    update: TaskUpdate,
+) -> tauri::Result<()> {
+    let legacy = legacy_events_enabled(app_handle);
+    emit_task_event_with(app_handle, legacy, legacy_event, legacy_payload, update)
+}
+
+/// 同 [`emit_task_event`]，兼容开关由调用方预先读取
+pub fn emit_task_event_with(
+    app_handle: &AppHandle,
+    legacy: bool,
+    legacy_event: &str,
+    legacy_payload: Value,
+    update: TaskUpdate,
+) -> tauri::Result<()> {
+    if legacy {
+        app_handle.emit(legacy_event, legacy_payload)?;
+    }
+    app_handle.emit(TASK_UPDATE_EVENT, update)
+}
+
+/// 只发送 `task_update` 事件，用于没有对应原有事件的阶段（如下载失败）
+pub fn emit_task_update(app_handle: &AppHandle, update: TaskUpdate) -> tauri::Result<()> {
+    app_handle.emit(TASK_UPDATE_EVENT, update)
+}
@@ -0,0 +1,239 @@
+//! 任务登记模块
+//! 在 tasks.dat 中记录尚未完成的下载任务，应用崩溃或退出后，
+//! 可凭临时目录中的 segments.json / progress.dat 继续下载。
+//! 任务参数同时写入临时目录的 task.json，tasks.dat 中缺失的任务可在扫描临时目录时找回。
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use tauri::AppHandle;
+use tauri_plugin_store::StoreExt;
+
+const TASK_STORE: &str = "tasks.dat";
+/// 临时目录中保存任务参数的文件
+const TASK_FILE: &str = "task.json";
+/// 临时目录名的前缀，与 `start_download` 中的约定一致
+const TEMP_DIR_PREFIX: &str = "temp_";
+/// 不写入磁盘的请求头（小写），避免 Cookie 和凭据以明文保存，恢复的任务需重新填写
+const SENSITIVE_HEADERS: [&str; 3] = ["cookie", "authorization", "proxy-authorization"];
+
+/// 串行化 tasks.dat 的读-改-写，避免多个任务同时登记时互相覆盖
+static TASK_LOCK: Mutex<()> = Mutex::new(());
+
+/// 恢复下载所需的任务参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskRecord {
+    pub id: String,
+    pub url: String,
+    pub name: String,
+    pub output_dir: String,
+    pub thread_count: usize,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+}
+
+impl TaskRecord {
+    /// 任务的临时目录，与 `start_download` 中的约定一致
+    pub fn temp_dir(&self) -> String {
+        format!("{}/{}{}", self.output_dir, TEMP_DIR_PREFIX, self.id)
+    }
+
+    /// 去掉敏感请求头后的副本，用于写入磁盘
+    fn redacted(&self) -> Self {
+        let headers = self
+            .headers
+            .iter()
+            .filter(|(name, _)| !SENSITIVE_HEADERS.contains(&name.to_lowercase().as_str()))
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        Self {
+            headers,
+            ..self.clone()
+        }
+    }
+}
+
+/// 可继续下载的任务
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IncompleteTask {
+    #[serde(flatten)]
+    pub record: TaskRecord,
+    /// 已完成的分片数
+    pub completed_segments: usize,
+    /// 分片总数，segments.json 无法解析时为 None
+    pub total_segments: Option<usize>,
+}
+
+/// 登记任务并立即保存，同时写入临时目录的 task.json
+///
+/// 敏感请求头不会保存。
+pub fn save_task(app_handle: &AppHandle, record: &TaskRecord) -> Result<()> {
+    let record = record.redacted();
+    let value = serde_json::to_value(&record)?;
+    let temp_dir = std::path::PathBuf::from(record.temp_dir());
+    if temp_dir.is_dir() {
+        std::fs::write(temp_dir.join(TASK_FILE), serde_json::to_vec_pretty(&value)?)?;
+    }
+
+    let _guard = TASK_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let store = app_handle.store(TASK_STORE)?;
+    store.set(record.id.clone(), value);
+    store.save()?;
+    Ok(())
+}
+
+/// 移除任务登记并立即保存
+pub fn remove_task(app_handle: &AppHandle, id: &str) -> Result<()> {
+    let _guard = TASK_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let store = app_handle.store(TASK_STORE)?;
+    if store.delete(id) {
+        store.save()?;
+    }
+    Ok(())
+}
+
+/// 把任务登记写入磁盘，退出应用前调用
+pub fn flush(app_handle: &AppHandle) -> Result<()> {
+    let _guard = TASK_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    app_handle.store(TASK_STORE)?.save()?;
+    Ok(())
+}
+
+/// 读取全部登记的任务，无法解析的记录会被忽略
+fn load_tasks(app_handle: &AppHandle) -> Result<Vec<TaskRecord>> {
+    let store = app_handle.store(TASK_STORE)?;
+    Ok(store
+        .values()
+        .into_iter()
+        .filter_map(|v| serde_json::from_value(v).ok())
+        .collect())
+}
+
+/// 所有登记的任务 ID，这些任务尚未完成，临时目录不应被清理
+pub fn registered_ids(app_handle: &AppHandle) -> Result<HashSet<String>> {
+    Ok(load_tasks(app_handle)?.into_iter().map(|r| r.id).collect())
+}
+
+/// 统计临时目录中的下载进度，目录中没有分片清单时返回 None
+async fn read_progress(temp_dir: &str) -> Option<(usize, Option<usize>)> {
+    let segments = tokio::fs::read_to_string(format!("{}/segments.json", temp_dir))
+        .await
+        .ok();
+    let progress = tokio::fs::read_to_string(format!("{}/progress.dat", temp_dir))
+        .await
+        .ok();
+    if segments.is_none() && progress.is_none() {
+        return None;
+    }
+
+    let completed = progress
+        .map(|p| p.lines().filter(|l| !l.trim().is_empty()).count())
+        .unwrap_or(0);
+    // 初始化分片不计入总数
+    let total = segments
+        .and_then(|s| serde_json::from_str::<Vec<serde_json::Value>>(&s).ok())
+        .map(|list| {
+            list.iter()
+                .filter(|s| !s.get("init").and_then(|v| v.as_bool()).unwrap_or(false))
+                .count()
+        });
+    Some((completed, total))
+}
+
+/// 扫描输出目录中未登记的 `temp_*` 临时目录，凭其中的 task.json 找回任务参数
+///
+/// 旧版本创建的临时目录没有 task.json，无法得知播放列表地址，跳过。
+async fn scan_temp_dirs(output_dir: &str, known: &HashSet<String>) -> Vec<TaskRecord> {
+    let mut found = Vec::new();
+    let Ok(mut entries) = tokio::fs::read_dir(output_dir).await else {
+        return found;
+    };
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let file_name = entry.file_name();
+        let Some(id) = file_name
+            .to_str()
+            .and_then(|name| name.strip_prefix(TEMP_DIR_PREFIX))
+        else {
+            continue;
+        };
+        if known.contains(id) || !entry.path().is_dir() {
+            continue;
+        }
+        let record = match tokio::fs::read(entry.path().join(TASK_FILE)).await {
+            Ok(content) => serde_json::from_slice::<TaskRecord>(&content).ok(),
+            Err(_) => None,
+        };
+        match record {
+            Some(record) if record.id == id => found.push(TaskRecord {
+                output_dir: output_dir.to_string(),
+                ..record
+            }),
+            _ => log::debug!("临时目录 {:?} 中没有可用的任务参数，跳过", entry.path()),
+        }
+    }
+    found
+}
+
+/// 查找可继续下载的任务
+///
+/// 除 tasks.dat 中登记的任务外，还会扫描下载目录和各任务输出目录中未登记的临时目录，
+/// 找回的任务重新登记。只返回临时目录仍带有分片清单的任务；临时目录已不存在的登记会被清除。
+pub async fn find_incomplete_tasks(app_handle: &AppHandle) -> Result<Vec<IncompleteTask>> {
+    let mut records = load_tasks(app_handle)?;
+    let mut known: HashSet<String> = records.iter().map(|r| r.id.clone()).collect();
+    let download_dir = app_handle
+        .store("settings.dat")
+        .ok()
+        .and_then(|s| s.get("downloadPath"))
+        .and_then(|v| v.as_str().map(|s| s.to_string()))
+        .filter(|dir| !dir.is_empty());
+    let output_dirs: HashSet<String> = records
+        .iter()
+        .map(|r| r.output_dir.clone())
+        .chain(download_dir)
+        .collect();
+    for output_dir in &output_dirs {
+        for record in scan_temp_dirs(output_dir, &known).await {
+            if read_progress(&record.temp_dir()).await.is_none() {
+                continue;
+            }
+            log::info!(
+                "任务 [{}] 在 {} 中找到未登记的临时目录，重新登记",
+                record.id,
+                output_dir
+            );
+            if let Err(e) = save_task(app_handle, &record) {
+                log::warn!("任务 [{}] 登记失败: {}", record.id, e);
+            }
+            known.insert(record.id.clone());
+            records.push(record);
+        }
+    }
+
+    let mut incomplete = Vec::new();
+    for record in records {
+        // 旧版本登记的记录可能带有敏感请求头，重新保存时去掉
+        let redacted = record.redacted();
+        if redacted.headers.len() != record.headers.len() {
+            if let Err(e) = save_task(app_handle, &redacted) {
+                log::warn!("任务 [{}] 更新登记失败: {}", record.id, e);
+            }
+        }
+        let record = redacted;
+        match read_progress(&record.temp_dir()).await {
+            Some((completed_segments, total_segments)) => incomplete.push(IncompleteTask {
+                record,
+                completed_segments,
+                total_segments,
+            }),
+            None => {
+                log::info!("任务 [{}] 临时目录已不存在，移除登记", record.id);
+                remove_task(app_handle, &record.id)?;
+            }
+        }
+    }
+    Ok(incomplete)
+}
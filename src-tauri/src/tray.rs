@@ -0,0 +1,66 @@
+//! 托盘提示模块
+//! 根据运行中的任务数和总进度更新托盘图标的提示文字，最小化到托盘时也能看到下载状态。
+
+use crate::download_manager::DownloadManager;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+
+/// 托盘图标 ID，与创建托盘时一致
+pub const TRAY_ID: &str = "tray";
+/// 没有运行中任务时的提示文字
+pub const DEFAULT_TOOLTIP: &str = "m3u8视频下载器";
+/// 下载进度更新托盘提示的最小间隔，多个任务的监控共用
+const PROGRESS_REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 上次设置的提示文字和按进度刷新的时间，文字不变时不重复设置
+struct TooltipState {
+    text: Option<String>,
+    last_progress_refresh: Option<Instant>,
+}
+
+static TOOLTIP_STATE: Mutex<TooltipState> = Mutex::new(TooltipState {
+    text: None,
+    last_progress_refresh: None,
+});
+
+/// 按运行中的任务数和总进度刷新托盘提示，如 "下载中 3 个任务 · 总进度 62%"
+///
+/// 任务增减时调用，立即生效。
+pub async fn refresh_tooltip(app_handle: &AppHandle) {
+    let Some(tray) = app_handle.tray_by_id(TRAY_ID) else {
+        return;
+    };
+    let manager = app_handle.state::<DownloadManager>();
+    let tooltip = match manager.overall_progress().await {
+        (0, _) => DEFAULT_TOOLTIP.to_string(),
+        (active, Some(progress)) => format!("下载中 {} 个任务 · 总进度 {}%", active, progress),
+        (active, None) => format!("下载中 {} 个任务", active),
+    };
+
+    let mut state = TOOLTIP_STATE.lock().unwrap_or_else(|e| e.into_inner());
+    if state.text.as_deref() == Some(tooltip.as_str()) {
+        return;
+    }
+    if let Err(e) = tray.set_tooltip(Some(&tooltip)) {
+        log::warn!("更新托盘提示失败: {}", e);
+        return;
+    }
+    state.text = Some(tooltip);
+}
+
+/// 下载进度变化时刷新托盘提示，按 [`PROGRESS_REFRESH_INTERVAL`] 节流
+pub async fn refresh_tooltip_throttled(app_handle: &AppHandle) {
+    {
+        let mut state = TOOLTIP_STATE.lock().unwrap_or_else(|e| e.into_inner());
+        let due = state
+            .last_progress_refresh
+            .map(|t| t.elapsed() >= PROGRESS_REFRESH_INTERVAL)
+            .unwrap_or(true);
+        if !due {
+            return;
+        }
+        state.last_progress_refresh = Some(Instant::now());
+    }
+    refresh_tooltip(app_handle).await;
+}
@@ -68,14 +68,27 @@ export const useDownloadedStore = defineStore("Downloaded", {
             return this.items.find((item) => item.id === id) || null;
         },
 
+        // 是否有下载项在合并后保留了临时目录（分片）
+        hasTempDir(ids) {
+            return ids.some((id) => this.getItemById(id)?.tempDir);
+        },
+
         // 移除下载项
-        async removeItem(id, isDeleteDownloadFile) {
+        // isDeleteTempDir: 是否删除合并后保留的临时目录，与是否删除原文件无关
+        async removeItem(id, isDeleteDownloadFile, isDeleteTempDir = true) {
             const item = this.getItemById(id);
             if (!item) return;
             this.items = this.items.filter((item) => item.id !== id);
             if (isDeleteDownloadFile) {
                 await invoke("delete_file", { filePath: item.file });
             }
+            // 记录移除后不会再有入口删除保留的临时目录
+            if (item.tempDir && isDeleteTempDir) {
+                await invoke("delete_download", {
+                    id: item.id,
+                    outputDir: item.downloadPath,
+                });
+            }
             this.selectedItems = this.selectedItems.filter((i) => i !== id);
             this.adjustCurrentPageAfterRemove();
         },
@@ -119,15 +132,24 @@ export const useDownloadedStore = defineStore("Downloaded", {
         async showFileInExplorer(id) {
             const file = this.getItemById(id)?.file;
             try {
-                await invoke("plugin:opener|reveal_item_in_dir", {
-                    paths: [file],
-                });
+                await invoke("reveal_file", { path: file });
                 console.log(`文件已在资源管理器中显示: ${file}`);
             } catch (error) {
                 this.$message.error("文件已被移动或删除");
                 console.error("无法在资源管理器中显示文件:", error);
             }
         },
+
+        // 打开文件所在的文件夹
+        async openFileFolder(id) {
+            const file = this.getItemById(id)?.file;
+            try {
+                await invoke("open_output_dir", { path: file });
+            } catch (error) {
+                this.$message.error("文件已被移动或删除");
+                console.error("无法打开文件夹:", error);
+            }
+        },
     },
     persist: true, // 启用持久化
 });
@@ -6,7 +6,7 @@ import { listen } from "@tauri-apps/api/event";
 
 export const useDownloadingStore = defineStore("Downloading", {
     /**
-   status 0-已取消 1-等待中 2-下载中 3-下载完成 4-合并中 5-合并完成 10-初始化或新添加 400-合并失败
+   status 0-已取消 1-等待中 2-下载中 3-下载完成 4-合并中 5-合并完成 6-已暂停 10-初始化或新添加 400-合并失败
    **/
 
     state: () => ({
@@ -50,6 +50,32 @@ export const useDownloadingStore = defineStore("Downloading", {
             }
         },
 
+        // 找回列表中缺失、但临时目录仍可续传的任务（如应用崩溃前未保存的任务）
+        async restoreIncompleteTasks() {
+            try {
+                const tasks = await invoke("resume_incomplete_tasks");
+                for (const task of tasks) {
+                    if (this.getItemById(task.id)) continue;
+                    this.addItem({
+                        id: task.id,
+                        title: task.name,
+                        progress: task.totalSegments
+                            ? Math.floor(
+                                  (task.completedSegments / task.totalSegments) *
+                                      100,
+                              )
+                            : 0,
+                        status: 10,
+                        url: task.url,
+                        downloadPath: task.outputDir,
+                        headers: task.headers,
+                    });
+                }
+            } catch (e) {
+                console.error("读取未完成任务失败", e);
+            }
+        },
+
         setCurrentPage(page) {
             this.pagination.currentPage = Math.max(
                 1,
@@ -89,22 +115,6 @@ export const useDownloadingStore = defineStore("Downloading", {
             return this.items.find((item) => item.id === id) || null;
         },
 
-        // 检查最大下载数，如果达到最大并发数，设置为等待状态
-        checkMaxDownloads(id) {
-            const settingStore = useSettingStore();
-            // 获取当前活跃任务数
-            const activeCount = this.items.filter(
-                (item) => item.status === 2, // 2 表示下载中
-            ).length;
-
-            // 如果达到最大并发数，设置为等待状态
-            if (activeCount >= settingStore.downloadCount) {
-                this.updateItem(id, { status: 1 }); // 1 表示等待中
-                return true;
-            }
-            return false;
-        },
-
         // 取消下载（保留临时目录，支持断点续传）
       async cancelDownload(id) {
             const item = this.getItemById(id);
@@ -113,8 +123,13 @@ export const useDownloadingStore = defineStore("Downloading", {
             // 清理该任务的所有事件监听器
             this.cleanupTaskListeners(id);
         
-            // 如果任务正在下载，调用后端取消
-            if (item.status === 2) {
+            // 如果任务正在排队、下载、已暂停或合并中，调用后端取消
+            if (
+                item.status === 1 ||
+                item.status === 2 ||
+                item.status === 4 ||
+                item.status === 6
+            ) {
               try {
                     await invoke("cancel_download", { id });
                 } catch (e) {
@@ -123,17 +138,37 @@ export const useDownloadingStore = defineStore("Downloading", {
             }
 
             // 更新状态为已取消
-            this.updateItem(id, { status: 0 }); // 0 表示已取消
+            this.updateItem(id, { status: 0, mergeProgress: null }); // 0 表示已取消
+        },
 
-            // 如果任务之前是活跃的，尝试启动等待队列中的下一个任务  
-            await this.tryStartNextDownloads();
+        // 暂停下载（后端保留任务，恢复后继续）
+        async pauseDownload(id) {
+            const item = this.getItemById(id);
+            if (!item || item.status !== 2) return;
+
+            try {
+                await invoke("pause_download", { id });
+                this.updateItem(id, { status: 6, speed: "0.00 KB/s" }); // 6 表示已暂停
+            } catch (e) {
+                console.error(`暂停任务 ${id} 失败:`, e);
+            }
+        },
+
+        // 恢复已暂停的下载
+        async resumeDownload(id) {
+            const item = this.getItemById(id);
+            if (!item || item.status !== 6) return;
+
+            try {
+                await invoke("resume_download", { id });
+                this.updateItem(id, { status: 2 });
+            } catch (e) {
+                console.error(`恢复任务 ${id} 失败:`, e);
+            }
         },
 
         // 继续下载（使用断点续传）
         async continueDownload(id) {
-            // 如果达到最大并发数，设置为等待状态
-            if (this.checkMaxDownloads(id)) return;
-
             const item = this.getItemById(id);
             if (item) {
                 // 直接调用 startDownload，后端会自动处理断点续传
@@ -141,11 +176,20 @@ export const useDownloadingStore = defineStore("Downloading", {
             }
         },
 
+        // 重新下载（丢弃断点续传数据，从头开始）
+        async redownload(id) {
+            const item = this.getItemById(id);
+            if (!item) return;
+
+            // 标记强制重新下载；后端取消旧任务并等待其退出后才清除临时目录
+            this.updateItem(id, { forceFresh: true });
+            await this.launchDownload(id, "restart_download");
+        },
+
         // 移除下载项并删除临时目录
         async removeItem(id) {
             const item = this.getItemById(id);
             if (!item) return;
-            const wasActive = item?.status === 2;
 
             try {
                 // outputDir 从 useSettingStore 中获取
@@ -164,10 +208,6 @@ export const useDownloadingStore = defineStore("Downloading", {
             this.items = this.items.filter((i) => i.id !== id);
             this.selectedItems = this.selectedItems.filter((i) => i !== id);
             this.adjustCurrentPageAfterRemove();
-
-            if (wasActive) {
-                await this.tryStartNextDownloads();
-            }
         },
 
         // 全选
@@ -215,28 +255,137 @@ export const useDownloadingStore = defineStore("Downloading", {
 
         // 启动新下载任务
         async startDownload(taskId) {
-            const settingStore = useSettingStore();
-            const downloadedStore = useDownloadedStore();
-          
             const currentItem = this.getItemById(taskId);
-            if (!currentItem || currentItem.status === 2) {
+            // 排队由后端负责，达到最大并发数时后端发出 download_queued 事件
+            if (!currentItem || currentItem.status === 1 || currentItem.status === 2) {
                 return; 
             }
-          
-            // 如果达到最大并发数，设置为等待状态
-            if (this.checkMaxDownloads(taskId)) return;
+
+            await this.launchDownload(taskId, "start_download");
+        },
+
+        // 注册监听器并调用后端开始下载；restart_download 会先等待旧任务完全停止
+        async launchDownload(taskId, command) {
+            const settingStore = useSettingStore();
 
             // 清理已有监听器
             this.cleanupTaskListeners(taskId);
           
             this.updateItem(taskId, { status: 2 });
           
+            await this.attachTaskListeners(taskId);
+
+            const item = this.getItemById(taskId);
+            if (item) {
+                const forceFresh = !!item.forceFresh;
+                this.updateItem(item.id, {
+                    isDownloaded: true,
+                    status: 2,
+                    forceFresh: false,
+                });
+                // 后端以单个 input 参数接收下载参数
+                await invoke(command, {
+                    input: {
+                        id: item.id,
+                        url: item.url,
+                        name: item.title,
+                        outputDir: item.downloadPath,
+                        threadCount: settingStore.threadCount,
+                        headers: item.headers || {},
+                        cookies: item.cookies || null,
+                        baseUrl: item.baseUrl || null,
+                        forceFresh,
+                    },
+                }).catch(async (err) => {
+                    await this.cancelDownload(item.id);
+                    this.$notify.error({
+                        content: this.getItemById(item.id).title + "下载失败",
+                        meta: err,
+                        keepAliveOnHover: true,
+                        duration: 5000,
+                    });
+                    console.error(this.getItemById(item.id).title + "下载失败")
+                });
+            }
+        },
+
+        // 批量开始下载：同一下载目录和请求头的任务一次提交，由后端按添加顺序排队
+        async batchDownload(taskIds, outputDir, headers) {
+            const settingStore = useSettingStore();
+            const ids = taskIds.filter((id) => {
+                const item = this.getItemById(id);
+                return item && item.status !== 1 && item.status !== 2;
+            });
+            if (ids.length === 0) return;
+
+            // 监听器须在提交前注册，否则会错过登记时发出的事件
+            for (const id of ids) {
+                this.cleanupTaskListeners(id);
+                await this.attachTaskListeners(id);
+                this.updateItem(id, {
+                    isDownloaded: true,
+                    status: 2,
+                    forceFresh: false,
+                });
+            }
+
+            const tasks = ids.map((id) => {
+                const item = this.getItemById(id);
+                return {
+                    id,
+                    url: item.url,
+                    name: item.title,
+                    cookies: item.cookies || null,
+                    baseUrl: item.baseUrl || null,
+                };
+            });
+            try {
+                const accepted = await invoke("batch_download", {
+                    tasks,
+                    outputDir,
+                    threadCount: settingStore.threadCount,
+                    headers: headers || {},
+                });
+                // 未被接受的任务（登记失败）恢复为已取消
+                for (const id of ids) {
+                    if (!accepted.includes(id)) {
+                        this.cleanupTaskListeners(id);
+                        this.updateItem(id, { status: 0 });
+                    }
+                }
+            } catch (err) {
+                for (const id of ids) {
+                    this.cleanupTaskListeners(id);
+                    this.updateItem(id, { status: 0 });
+                }
+                this.$notify.error({
+                    content: "批量下载失败",
+                    meta: err,
+                    keepAliveOnHover: true,
+                    duration: 5000,
+                });
+                console.error("批量下载失败", err);
+            }
+        },
+
+        // 注册任务的全部事件监听器
+        async attachTaskListeners(taskId) {
+            const downloadedStore = useDownloadedStore();
+
             // 创建事件监听器
             const [
                 listenCreateDir,
                 listenProgress,
+                listenPreparing,
                 listenStartMerge,
                 listenMerge,
+                listenThumbnail,
+                listenMissing,
+                listenSkipped,
+                listenMergeWarning,
+                listenQueued,
+                listenDiskSpace,
+                listenLive,
             ] = await Promise.all([
                 // 创建临时目录监听
                 listen("create_temp_directory", (event) => {
@@ -257,25 +406,52 @@ export const useDownloadingStore = defineStore("Downloading", {
                     const data = event.payload;
                     if (data.id === taskId) {
                       const currentItem = this.getItemById(data.id);
-                      if (currentItem && currentItem.status === 2) {
+                      // 暂停/恢复由后端上报状态 6/2
+                      if (
+                          currentItem &&
+                          (currentItem.status === 2 || currentItem.status === 6)
+                      ) {
                           this.updateItem(data.id, { ...data });
                       }
                     }
                 }),
 
+                // 准备阶段进度监听（解析播放列表、获取密钥、检查已下载分片），显示在速度位置
+                listen("download_preparing", (event) => {
+                    const data = event.payload;
+                    if (data.id === taskId) {
+                        const currentItem = this.getItemById(data.id);
+                        // 排队的任务开始后首先发出该事件，此时切换为下载中
+                        if (
+                            currentItem &&
+                            (currentItem.status === 1 || currentItem.status === 2)
+                        ) {
+                            this.updateItem(data.id, {
+                                status: 2,
+                                speed: data.message,
+                            });
+                        }
+                    }
+                }),
+
                 // 开始合并监听
                 listen("start_merge_video", (event) => {
                     const data = event.payload;
                     if (data.id === taskId) {
-                        this.updateItem(data.id, { status: 4 });
+                        this.updateItem(data.id, { status: 4, mergeProgress: null });
                     }
-                    // 开始合并，触发队列检查，继续下载下一个
-                    this.tryStartNextDownloads();
                 }),
 
                 // 合并视频监听
                 listen("merge_video", (event) => {
                     const data = event.payload;
+                    // 合并进度，总时长未知时 mergeProgress 为 null
+                    if (data.id === taskId && data.status === 4) {
+                        this.updateItem(taskId, {
+                            mergeProgress: data.mergeProgress,
+                        });
+                        return;
+                    }
                     if (data.id === taskId && data.isMerged) {
                         // 迁移数据示例
                         const item = this.items.find((i) => i.id === taskId);
@@ -292,62 +468,112 @@ export const useDownloadingStore = defineStore("Downloading", {
                         }
                     }
                 }),
+
+                // 封面监听（开启生成封面时，在合并成功之前发出）
+                listen("thumbnail_generated", (event) => {
+                    const data = event.payload;
+                    if (data.id === taskId) {
+                        this.updateItem(taskId, { thumbnail: data.thumbnail });
+                    }
+                }),
+
+                // 缺失分片监听（容忍缺失分片模式下跳过的分片）
+                listen("segments_missing", (event) => {
+                    const data = event.payload;
+                    if (data.id === taskId) {
+                        const item = this.getItemById(taskId);
+                        this.updateItem(taskId, {
+                            missingSegments: data.missing_segments,
+                        });
+                        this.$notify.warning({
+                            content: (item ? item.title : "") + data.message,
+                            meta: "缺失分片序号: " + data.missing_segments.join(", "),
+                            keepAliveOnHover: true,
+                            duration: 8000,
+                        });
+                    }
+                }),
+
+                // 无效分片监听（内容为空或无效而被跳过的分片）
+                listen("segments_skipped", (event) => {
+                    const data = event.payload;
+                    if (data.id === taskId) {
+                        const item = this.getItemById(taskId);
+                        this.updateItem(taskId, {
+                            skippedSegments: data.skipped_segments,
+                        });
+                        this.$notify.warning({
+                            content: (item ? item.title : "") + data.message,
+                            meta: "无效分片序号: " + data.skipped_segments.join(", "),
+                            keepAliveOnHover: true,
+                            duration: 8000,
+                        });
+                    }
+                }),
+
+                // 警告监听（自动重新编码、未使用 ffmpeg 直接拼接、音频轨道无法录制等）
+                listen("merge_warning", (event) => {
+                    const data = event.payload;
+                    if (data.id === taskId) {
+                        const item = this.getItemById(taskId);
+                        this.$notify.warning({
+                            content: item ? item.title : "合并提示",
+                            meta: data.message,
+                            keepAliveOnHover: true,
+                            duration: 8000,
+                        });
+                    }
+                }),
+
+                // 排队监听（后端达到最大并发数时），显示排队位置
+                listen("download_queued", (event) => {
+                    const data = event.payload;
+                    if (data.id === taskId) {
+                        this.updateItem(taskId, {
+                            status: 1, // 1 表示等待中
+                            speed: data.message,
+                        });
+                    }
+                }),
+
+                // 磁盘空间不足监听（下载前检查失败或下载中自动暂停）
+                listen("disk_space_low", (event) => {
+                    const data = event.payload;
+                    if (data.id === taskId) {
+                        const item = this.getItemById(taskId);
+                        this.$notify.warning({
+                            content: item ? item.title : "磁盘空间不足",
+                            meta: data.message,
+                            keepAliveOnHover: true,
+                            duration: 8000,
+                        });
+                    }
+                }),
+
+                // 直播录制进度监听，显示已录制的分片数
+                listen("download_live", (event) => {
+                    const data = event.payload;
+                    if (data.id === taskId) {
+                        this.updateItem(taskId, { speed: data.message });
+                    }
+                }),
             ]);
 
             // 存储监听器
             this.taskListeners[taskId] = [
                 listenCreateDir,
                 listenProgress,
+                listenPreparing,
                 listenStartMerge,
                 listenMerge,
+                listenThumbnail,
+                listenMissing,
+                listenSkipped,
+                listenMergeWarning,
+                listenQueued,
+                listenDiskSpace,
+                listenLive,
             ];
-
-            const item = this.getItemById(taskId);
-            if (item) {
-                this.updateItem(item.id, { isDownloaded: true, status: 2 });
-                await invoke("start_download", {
-                    id: item.id,
-                    url: item.url,
-                    name: item.title,
-                    outputDir: item.downloadPath,
-                    threadCount: settingStore.threadCount,
-                    headers: item.headers || {},
-                }).catch(async (err) => {
-                    await this.cancelDownload(item.id);
-                    this.$notify.error({
-                        content: this.getItemById(item.id).title + "下载失败",
-                        meta: err,
-                        keepAliveOnHover: true,
-                        duration: 5000,
-                    });
-                    console.error(this.getItemById(item.id).title + "下载失败")
-                });
-            }
-        },
-
-        // 尝试启动等待中的任务
-        async tryStartNextDownloads() {
-            const settingStore = useSettingStore();
-
-            // 计算可用槽位
-            const activeCount = this.items.filter(
-                (item) => item.status === 2,
-            ).length;
-            const availableSlots = settingStore.downloadCount - activeCount;
-
-            if (availableSlots <= 0) return;
-
-            // 找到前 N 个等待中的任务
-            const waitingTasks = this.items
-                .filter((item) => item.status === 1)
-                .slice(0, availableSlots);
-
-            // 启动这些任务
-            for (const task of waitingTasks) {
-              if(task.status === 1) {
-                this.startDownload(task.id);
-              }
-            }
         },
     },
     persist: true, // 启用持久化
@@ -9,7 +9,39 @@ export const useSettingStore = defineStore("Setting", {
         logicalCores: 4, // 逻辑核心数
         isDeleteDownloadFile: false, // 是否删除下载原文件
         minimizeOnClose: true, // false 退出程序  true 最小化
+        notificationsEnabled: true, // 下载完成或失败时发送桌面通知
         logLevel: "Info", // 日志级别
+        maxLogSizeMB: 50, // 单个日志文件大小上限（MB），超过后滚动，0 不限制
+        maxLogParts: 5, // 每天最多保留的滚动日志文件数
+        progressIntervalMs: 1000, // 下载进度刷新间隔（毫秒）
+        maxRedirects: 10, // 单个请求最大重定向次数
+        segmentTimeoutSecs: 60, // 单个分片请求超时（秒）
+        maxRetries: 99, // 单个分片最大尝试次数
+        maxBackoffSecs: 10, // 重试退避等待上限（秒）
+        deterministicBackoff: false, // 重试退避不加随机抖动，便于复现问题
+        failFast: true, // 分片用尽重试后立即取消整个任务
+        tolerateMissing: false, // 跳过最终失败的分片继续合并
+        skipInvalidSegments: false, // 跳过内容为空或无效的分片继续合并
+        ignoreSegmentQuery: false, // 续传追加新分片时忽略分片地址的查询参数（轮换的签名 token）
+        checkDiskSpace: true, // 下载前检查磁盘剩余空间
+        minFreeSpaceMB: 512, // 下载中剩余空间低于该值（MB）时暂停
+        segmentSizeEstimateMB: 2, // 无法获取分片大小时的单分片估算（MB）
+        liveRecording: false, // 录制直播流直到结束，关闭时遇到直播流报错
+        proxy: "", // 代理地址，如 http://127.0.0.1:7890 或 socks5://127.0.0.1:1080
+        dangerAcceptInvalidCerts: false, // 不校验 TLS 证书，仅用于自签名证书的局域网服务器
+        userAgent: "", // 自定义 User-Agent，留空使用浏览器默认值
+        speedLimitKBps: 0, // 单个任务限速（KB/s），0 不限速
+        maxConnectionsPerHost: 0, // 单个主机的最大连接数，0 与下载线程数相同
+        quality: "highest", // 主播放列表的画质选择
+        audioLanguage: "", // 音频单独存放时优先选择的语言
+        outputFormat: "mp4", // 输出格式：mp4/mkv/ts
+        autoReencode: true, // 分片编码无法直接封装时自动重新编码
+        timestampMode: "auto", // 合并时的时间戳处理：auto/off/genpts/copyts
+        overwriteExisting: false, // 同名文件已存在时覆盖，关闭时在文件名后追加序号
+        generateThumbnail: false, // 合并完成后截取一帧保存为同名 jpg 封面
+        keepTemp: false, // 合并成功后保留临时目录中的分片
+        useSystemFfmpeg: false, // 使用系统安装的 ffmpeg
+        ffmpegPath: "", // 系统 ffmpeg 路径，留空在 PATH 中查找
     }),
     actions: {},
     persist: true, // 启用持久化